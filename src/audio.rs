@@ -0,0 +1,253 @@
+//! Sanity checks for downloaded audio, so that error pages and truncated
+//! streams don't get tagged as tracks.
+
+use crate::types::Duration;
+
+/// Decoded and expected durations may differ by this fraction...
+const DURATION_TOLERANCE: f64 = 0.05;
+
+/// ...or by this much, whichever is more lenient
+const MIN_DURATION_TOLERANCE: Duration = Duration::from_secs(2);
+
+/// Anything shorter than this many frames isn't a track
+const MIN_FRAMES: usize = 10;
+
+/// How far into the file (after any ID3 tag) the first frame may start
+const MAX_SYNC_OFFSET: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum InvalidAudio {
+    /// The server said it sent us something other than audio
+    ContentType(String),
+
+    Empty,
+
+    /// No run of MPEG audio frames could be found
+    NoFrames,
+
+    /// The decoded duration doesn't match what the player data says
+    Duration {
+        expected: Duration,
+        actual: Duration,
+    },
+}
+
+impl std::fmt::Display for InvalidAudio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ContentType(content_type) => {
+                write!(f, "unexpected content type '{content_type}'")
+            }
+            Self::Empty => write!(f, "empty file"),
+            Self::NoFrames => write!(f, "no MP3 frames found"),
+            Self::Duration { expected, actual } => write!(
+                f,
+                "decoded duration {:.1}s doesn't match expected {:.1}s",
+                actual.as_secs_f64(),
+                expected.as_secs_f64()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InvalidAudio {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Frame {
+    len: usize,
+    samples: u32,
+    sample_rate: u32,
+}
+
+/// Parses an MPEG audio frame header.
+fn frame(header: &[u8]) -> Option<Frame> {
+    const BITRATES_V1: [[u32; 14]; 3] = [
+        [
+            32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448,
+        ],
+        [
+            32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384,
+        ],
+        [
+            32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+        ],
+    ];
+    const BITRATES_V2: [[u32; 14]; 2] = [
+        [
+            32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256,
+        ],
+        [8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+    ];
+    const SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+    let [b0, b1, b2, ..] = *header else {
+        return None;
+    };
+
+    if b0 != 0xFF || b1 & 0xE0 != 0xE0 {
+        return None;
+    }
+
+    // 0 = MPEG 2.5, 2 = MPEG 2, 3 = MPEG 1
+    let version = (b1 >> 3) & 0b11;
+    // 1 = layer III, 2 = layer II, 3 = layer I
+    let layer = (b1 >> 1) & 0b11;
+    let bitrate_index = (b2 >> 4) as usize;
+    let sample_rate_index = ((b2 >> 2) & 0b11) as usize;
+    let padding = ((b2 >> 1) & 1) as usize;
+
+    // free-format bitrates aren't worth the trouble
+    if version == 1 || layer == 0 || !(1..15).contains(&bitrate_index) || sample_rate_index == 3 {
+        return None;
+    }
+
+    let bitrate = match (version, layer) {
+        (3, _) => BITRATES_V1[3 - layer as usize][bitrate_index - 1],
+        (_, 3) => BITRATES_V2[0][bitrate_index - 1],
+        _ => BITRATES_V2[1][bitrate_index - 1],
+    } * 1000;
+
+    let sample_rate = SAMPLE_RATES[sample_rate_index]
+        / match version {
+            3 => 1,
+            2 => 2,
+            _ => 4,
+        };
+
+    let samples = match (version, layer) {
+        (_, 3) => 384,
+        (3, _) | (_, 2) => 1152,
+        _ => 576,
+    };
+
+    let len = if layer == 3 {
+        (12 * bitrate / sample_rate) as usize * 4 + padding * 4
+    } else {
+        (samples / 8 * bitrate / sample_rate) as usize + padding
+    };
+
+    Some(Frame {
+        len,
+        samples,
+        sample_rate,
+    })
+}
+
+/// Length of a leading ID3v2 tag, if there is one.
+fn id3_len(data: &[u8]) -> usize {
+    match data {
+        [b'I', b'D', b'3', _, _, flags, size @ ..] if size.len() >= 4 => {
+            let size = size[..4]
+                .iter()
+                .fold(0usize, |acc, b| (acc << 7) | (b & 0x7F) as usize);
+            let footer = if flags & 0x10 != 0 { 10 } else { 0 };
+            10 + size + footer
+        }
+        _ => 0,
+    }
+}
+
+/// Walks the MPEG frames in `data`, returning the total playing time.
+pub(crate) fn mp3_duration(data: &[u8]) -> Option<Duration> {
+    let start = id3_len(data).min(data.len());
+    let data = &data[start..];
+
+    // skip any junk before the first frame, as long as the frame after it
+    // is where it should be
+    let mut pos = (0..data.len().min(MAX_SYNC_OFFSET)).find(|&i| {
+        frame(&data[i..])
+            .and_then(|f| frame(data.get(i + f.len..)?))
+            .is_some()
+    })?;
+
+    let mut frames = 0;
+    let mut seconds = 0.0;
+
+    while let Some(f) = frame(&data[pos..]) {
+        if pos + f.len > data.len() {
+            break;
+        }
+
+        frames += 1;
+        seconds += f.samples as f64 / f.sample_rate as f64;
+        pos += f.len;
+    }
+
+    (frames >= MIN_FRAMES).then(|| Duration::from_secs_f64(seconds))
+}
+
+/// Checks that a download looks like the track it's meant to be.
+pub(crate) fn validate(
+    data: &[u8],
+    content_type: Option<&str>,
+    expected: Duration,
+) -> Result<Duration, InvalidAudio> {
+    if let Some(content_type) = content_type {
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        if !(essence.starts_with("audio/") || essence == "application/octet-stream") {
+            return Err(InvalidAudio::ContentType(content_type.to_owned()));
+        }
+    }
+
+    if data.is_empty() {
+        return Err(InvalidAudio::Empty);
+    }
+
+    let actual = mp3_duration(data).ok_or(InvalidAudio::NoFrames)?;
+
+    if !expected.is_zero() {
+        let tolerance = expected
+            .mul_f64(DURATION_TOLERANCE)
+            .max(MIN_DURATION_TOLERANCE);
+
+        if actual.abs_diff(expected) > tolerance {
+            return Err(InvalidAudio::Duration { expected, actual });
+        }
+    }
+
+    Ok(actual)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// MPEG 1 layer III, 128kbps, 44.1kHz: 417 byte frames of 1152 samples
+    fn mp3(frames: usize) -> Vec<u8> {
+        let mut frame = vec![0xFF, 0xFB, 0x90, 0x00];
+        frame.resize(417, 0);
+        frame.repeat(frames)
+    }
+
+    #[test]
+    fn validation() {
+        let data = mp3(383);
+        let duration = mp3_duration(&data).unwrap();
+        assert_eq!(10, duration.as_secs());
+
+        let mut tagged = b"ID3\x04\x00\x00\x00\x00\x00\x05hello".to_vec();
+        tagged.extend(&data);
+        assert_eq!(Some(duration), mp3_duration(&tagged));
+
+        assert_eq!(
+            Ok(duration),
+            validate(&data, Some("audio/mpeg"), Duration::from_secs(10))
+        );
+        assert_eq!(
+            Err(InvalidAudio::ContentType("text/html; charset=utf-8".into())),
+            validate(b"<html>", Some("text/html; charset=utf-8"), Duration::ZERO)
+        );
+        assert_eq!(
+            Err(InvalidAudio::Empty),
+            validate(b"", None, Duration::ZERO)
+        );
+        assert_eq!(
+            Err(InvalidAudio::NoFrames),
+            validate(b"<html>oops</html>", None, Duration::ZERO)
+        );
+        assert!(matches!(
+            validate(&mp3(100), None, Duration::from_secs(10)),
+            Err(InvalidAudio::Duration { .. })
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+use crate::bandcamp::{self, BlogPost};
+use crate::state;
+use crate::types::DateTime;
+use chrono::NaiveDate;
+use std::path::PathBuf;
+
+const CURSOR_FILENAME: &str = "backfill.json";
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Cursor {
+    until: NaiveDate,
+    page: usize,
+    finished: bool,
+}
+
+/// Walks the blog archive page by page, newest to oldest, until it reaches
+/// posts published before `until`.
+///
+/// Progress is persisted after each page so that an interrupted backfill
+/// picks up where it left off.
+#[derive(Debug)]
+pub(crate) struct Backfill {
+    cursor: Cursor,
+    path: PathBuf,
+    oldest: Option<DateTime>,
+}
+
+/// Picks up a saved cursor with the target date given this time.
+fn resume(prev: Option<Cursor>, until: NaiveDate) -> Cursor {
+    match prev {
+        // everything newer than the previous target has already been
+        // scanned, so keep going from the last page we processed
+        Some(prev) if until < prev.until => Cursor {
+            until,
+            page: prev.page,
+            finished: false,
+        },
+        Some(prev) if prev.finished => prev,
+        // an unfinished walk stops at the newer target instead
+        Some(prev) => {
+            if prev.until != until {
+                tracing::info!(from = %prev.until, to = %until, "changing backfill target date");
+            }
+            Cursor { until, ..prev }
+        }
+        None => Cursor {
+            until,
+            page: 1,
+            finished: false,
+        },
+    }
+}
+
+impl Backfill {
+    pub(crate) fn load(dir: &str, until: NaiveDate) -> anyhow::Result<Self> {
+        let path = PathBuf::from(dir).join(CURSOR_FILENAME);
+
+        let cursor = resume(state::load(&path).ok(), until);
+
+        if cursor.page > 1 {
+            tracing::info!(page = cursor.page, "resuming archive backfill");
+        }
+
+        Ok(Self {
+            cursor,
+            path,
+            oldest: None,
+        })
+    }
+
+    /// Fetches the next page of post URLs, or `None` once the backfill is done.
+    pub(crate) async fn next_page(
+        &mut self,
+        client: &crate::http::Client,
+    ) -> anyhow::Result<Option<Vec<String>>> {
+        if self.cursor.finished {
+            return Ok(None);
+        }
+
+        let url = bandcamp::archive_page_url(self.cursor.page);
+        tracing::info!(page = self.cursor.page, url, "fetching archive page");
+
+        // the archive grows at the front, so a cached page would be out of step
+        let (html, _) = client.fetch_text_fresh(&url).await?;
+
+        let urls = bandcamp::archive_post_urls(&html);
+        if urls.is_empty() {
+            tracing::info!("reached the end of the archive");
+            self.finish()?;
+            return Ok(None);
+        }
+
+        self.oldest = None;
+        Ok(Some(urls))
+    }
+
+    pub(crate) fn saw(&mut self, post: &BlogPost) {
+        if self.oldest.is_none_or(|oldest| post.published < oldest) {
+            self.oldest = Some(post.published);
+        }
+    }
+
+    pub(crate) fn is_before_cutoff(&self, post: &BlogPost) -> bool {
+        post.published.date_naive() < self.cursor.until
+    }
+
+    /// Records the current page as done and persists the cursor.
+    pub(crate) fn advance(&mut self) -> anyhow::Result<()> {
+        self.cursor.page += 1;
+
+        if self
+            .oldest
+            .is_some_and(|oldest| oldest.date_naive() < self.cursor.until)
+        {
+            tracing::info!(until = %self.cursor.until, "backfill reached target date");
+            return self.finish();
+        }
+
+        state::save(&self.cursor, &self.path)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.cursor.finished = true;
+        state::save(&self.cursor, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_with_new_date() {
+        let date = |s: &str| s.parse::<NaiveDate>().unwrap();
+        let cursor = |until, page, finished| Cursor {
+            until: date(until),
+            page,
+            finished,
+        };
+
+        assert_eq!(
+            cursor("2024-01-01", 1, false),
+            resume(None, date("2024-01-01"))
+        );
+
+        // an older target carries on, even after finishing
+        assert_eq!(
+            cursor("2023-01-01", 7, false),
+            resume(Some(cursor("2024-01-01", 7, true)), date("2023-01-01"))
+        );
+
+        // a newer target replaces the old one for an unfinished walk
+        assert_eq!(
+            cursor("2024-06-01", 7, false),
+            resume(Some(cursor("2024-01-01", 7, false)), date("2024-06-01"))
+        );
+        assert_eq!(
+            cursor("2024-01-01", 7, true),
+            resume(Some(cursor("2024-01-01", 7, true)), date("2024-06-01"))
+        );
+    }
+}
@@ -1,18 +1,30 @@
 use crate::types::{DateTime, Duration, Track};
 use anyhow::Context;
-use scraper::{Html, Selector};
+use scraper::{ElementRef, Html, Selector};
 use serde_json as json;
-use std::sync::LazyLock;
+
+const PLAYER_INFOS: &str = "data-player-infos";
+
+/// Each entry in `data-player-infos` is rendered into one of these, in order
+const PLAYER_SLOT: &str = "mplayer";
 
 pub(crate) const FEED_URL: &str = "https://daily.bandcamp.com/feed/";
+pub(crate) const BASE_URL: &str = "https://daily.bandcamp.com";
+const ARCHIVE_URL: &str = "https://daily.bandcamp.com/latest";
+
+/// Used to estimate reading time
+const WORDS_PER_MINUTE: usize = 230;
+
+/// Top-level paths on the blog that are listings rather than articles
+const NON_ARTICLE_SECTIONS: &[&str] = &["latest", "tag", "tags", "search", "about", "feed"];
 
-struct HtmlSelector {
+pub(crate) struct HtmlSelector {
     text: String,
     selector: Selector,
 }
 
 impl HtmlSelector {
-    fn try_new(s: &str) -> anyhow::Result<Self> {
+    pub(crate) fn try_new(s: &str) -> anyhow::Result<Self> {
         match Selector::parse(s) {
             Ok(sel) => Ok(Self {
                 text: s.to_string(),
@@ -24,27 +36,29 @@ impl HtmlSelector {
         }
     }
 
-    fn try_new_meta(name: &str) -> anyhow::Result<Self> {
+    pub(crate) fn try_new_meta(name: &str) -> anyhow::Result<Self> {
         let s = format!(r#"meta[property="{name}"]"#);
         Self::try_new(s.as_str())
     }
 
-    fn select<'a, 'b>(&'a self, doc: &'b Html) -> scraper::html::Select<'b, 'a> {
+    pub(crate) fn select<'a, 'b>(&'a self, doc: &'b Html) -> scraper::html::Select<'b, 'a> {
         doc.select(&self.selector)
     }
 }
 
 macro_rules! selector {
-    ($name:ident, $s:expr) => {
-        selector!($name, $s, HtmlSelector::try_new);
+    ($vis:vis $name:ident, $s:expr) => {
+        $crate::bandcamp::selector!($vis $name, $s, $crate::bandcamp::HtmlSelector::try_new);
     };
-    ($name:ident, $s:expr, $fn:path) => {
-        static $name: LazyLock<HtmlSelector> =
-            LazyLock::new(|| $fn($s).expect("invalid CSS selector"));
+    ($vis:vis $name:ident, $s:expr, $fn:path) => {
+        $vis static $name: std::sync::LazyLock<$crate::bandcamp::HtmlSelector> =
+            std::sync::LazyLock::new(|| $fn($s).expect("invalid CSS selector"));
     };
 }
+pub(crate) use selector;
 
 selector!(DAILY_ARTICLE, "#p-daily-article");
+selector!(LINK, "a[href]");
 selector!(META_TITLE, "og:title", HtmlSelector::try_new_meta);
 selector!(META_URL, "og:url", HtmlSelector::try_new_meta);
 selector!(
@@ -62,6 +76,16 @@ selector!(
     "og:description",
     HtmlSelector::try_new_meta
 );
+selector!(META_IMAGE, "og:image", HtmlSelector::try_new_meta);
+selector!(META_SECTION, "article:section", HtmlSelector::try_new_meta);
+selector!(META_TAG, "article:tag", HtmlSelector::try_new_meta);
+selector!(
+    META_AUTHOR,
+    r#"meta[property="article:author"], meta[name="author"]"#
+);
+selector!(BYLINE, "article-credits");
+selector!(TITLE, "title");
+selector!(pub(crate) IFRAME, "iframe[src]");
 
 #[derive(Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct TrackInfo {
@@ -78,11 +102,38 @@ pub(crate) struct TrackInfo {
 }
 
 impl TrackInfo {
-    fn download_url(&self) -> Option<String> {
+    pub(crate) fn download_url(&self) -> Option<String> {
         self.audio_url.last_key_value().map(|(_, v)| v.to_owned())
     }
 }
 
+/// The page no longer has the structure the scraper relies on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LayoutError {
+    /// No element matched a required selector
+    MissingElement(String),
+
+    /// A `<meta>` tag is missing or has no content
+    MissingMeta(String),
+
+    /// An attribute or `<meta>` tag is present but can't be parsed
+    Invalid { source: String, reason: String },
+}
+
+impl std::fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingElement(selector) => write!(f, "no element matches '{selector}'"),
+            Self::MissingMeta(selector) => {
+                write!(f, "no metadata content found for '{selector}'")
+            }
+            Self::Invalid { source, reason } => write!(f, "invalid content in {source}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
 #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub(crate) struct PlayerData {
     pub(crate) title: String,
@@ -94,6 +145,9 @@ pub(crate) struct PlayerData {
     pub(crate) band_location: Option<String>,
     pub(crate) band_url: Option<String>,
     pub(crate) tralbum_url: Option<String>,
+
+    #[serde(default, alias = "album_art_id")]
+    pub(crate) art_id: Option<u64>,
 }
 
 impl PlayerData {
@@ -101,42 +155,70 @@ impl PlayerData {
         self.tracklist
             .iter()
             .find(|&ti| ti.track_number == self.featured_track_number)
+            .map(|ti| self.make_track(ti, playlist_index))
+    }
+
+    /// With `full_album`, every track of the release; otherwise only the
+    /// featured one.
+    pub(crate) fn get_tracks(&self, playlist_index: usize, full_album: bool) -> Vec<Track> {
+        if !full_album {
+            return self.get_track(playlist_index).into_iter().collect();
+        }
+
+        self.tracklist
+            .iter()
             .map(|ti| Track {
-                title: ti.track_title.clone(),
-                artist: crate::types::Artist {
-                    name: ti.artist.clone(),
-                    bandcamp_id: if ti.artist == self.band_name {
-                        self.band_id.map(|id| id.to_string())
-                    } else {
-                        None
-                    },
-                    bandcamp_url: if ti.artist == self.band_name {
-                        self.band_url.clone()
-                    } else {
-                        None
-                    },
-                    spotify_id: None,
-                },
-                album_artist: crate::types::Artist {
-                    name: self.band_name.clone(),
-                    bandcamp_id: self.band_id.map(|id| id.to_string()),
-                    bandcamp_url: self.band_url.clone(),
-                    spotify_id: None,
+                full_album: true,
+                ..self.make_track(ti, playlist_index)
+            })
+            .collect()
+    }
+
+    fn make_track(&self, ti: &TrackInfo, playlist_index: usize) -> Track {
+        Track {
+            title: ti.track_title.clone(),
+            artist: crate::types::Artist {
+                name: ti.artist.clone(),
+                bandcamp_id: if ti.artist == self.band_name {
+                    self.band_id.map(|id| id.to_string())
+                } else {
+                    None
                 },
-                album: crate::types::Album {
-                    title: self.title.clone(),
-                    bandcamp_id: ti.album_id.map(|id| id.to_string()),
-                    bandcamp_url: self.tralbum_url.clone(),
-                    spotify_id: None,
+                bandcamp_url: if ti.artist == self.band_name {
+                    self.band_url.clone()
+                } else {
+                    None
                 },
-                duration: ti.audio_track_duration,
-                number: ti.track_number,
-                download_url: ti.download_url(),
-                bandcamp_track_id: ti.track_id.map(|id| id.to_string()),
                 spotify_id: None,
-                spotify_playlist_id: None,
-                bandcamp_playlist_track_number: playlist_index,
-            })
+            },
+            album_artist: crate::types::Artist {
+                name: self.band_name.clone(),
+                bandcamp_id: self.band_id.map(|id| id.to_string()),
+                bandcamp_url: self.band_url.clone(),
+                spotify_id: None,
+            },
+            album: crate::types::Album {
+                title: self.title.clone(),
+                bandcamp_id: ti.album_id.map(|id| id.to_string()),
+                bandcamp_url: self.tralbum_url.clone(),
+                spotify_id: None,
+                art_id: self.art_id,
+                details: None,
+            },
+            duration: ti.audio_track_duration,
+            number: ti.track_number,
+            download_url: ti.download_url(),
+            download_url_fetched: None,
+            bandcamp_track_id: ti.track_id.map(|id| id.to_string()),
+            spotify_id: None,
+            spotify_playlist_id: None,
+            bandcamp_playlist_track_number: playlist_index,
+            full_album: false,
+            lyrics: None,
+            blurb: None,
+            invalid_download: None,
+            download_failures: None,
+        }
     }
 }
 
@@ -146,33 +228,87 @@ pub(crate) struct TrackList {
     pub(crate) raw: Vec<json::Value>,
 }
 
+/// Collects the text of the paragraphs that follow each player slot within
+/// `root`, indexed by slot.
+pub(crate) fn blurbs<F>(root: ElementRef, is_slot: F) -> Vec<Option<String>>
+where
+    F: Fn(&ElementRef) -> bool,
+{
+    let mut blurbs: Vec<Vec<String>> = vec![];
+
+    for elem in root.descendants().filter_map(ElementRef::wrap) {
+        if is_slot(&elem) {
+            blurbs.push(vec![]);
+            continue;
+        }
+
+        if elem.value().name() != "p" {
+            continue;
+        }
+
+        let Some(current) = blurbs.last_mut() else {
+            continue;
+        };
+
+        let text = elem.text().collect::<String>();
+        let text = collapse_whitespace(&text);
+        if !text.is_empty() {
+            current.push(text);
+        }
+    }
+
+    blurbs
+        .into_iter()
+        .map(|paragraphs| Some(paragraphs.join("\n\n")).filter(|text| !text.is_empty()))
+        .collect()
+}
+
 impl TrackList {
-    pub(crate) fn try_from_html(doc: &Html) -> anyhow::Result<Self> {
+    pub(crate) fn try_from_html(doc: &Html, full_album: bool) -> anyhow::Result<Self> {
         let article = &*DAILY_ARTICLE;
 
+        if article.select(doc).next().is_none() {
+            return Err(LayoutError::MissingElement(article.text.clone()).into());
+        }
+
         let mut list = TrackList::default();
 
         let mut idx = 0;
 
-        for matched in article
-            .select(doc)
-            .filter_map(|elem| elem.attr("data-player-infos"))
-        {
+        for elem in article.select(doc) {
+            // not every post has players
+            let Some(matched) = elem.attr(PLAYER_INFOS) else {
+                continue;
+            };
+
             let infos: Vec<json::Value> =
-                json::from_str(matched).context("parsing 'data-player-infos' as JSON")?;
+                json::from_str(matched).map_err(|e| LayoutError::Invalid {
+                    source: format!("'{PLAYER_INFOS}'"),
+                    reason: e.to_string(),
+                })?;
 
             list.raw.extend(infos.clone());
 
-            for info in infos.into_iter().filter(|v| !v.is_null()) {
-                let info: PlayerData = json::from_value(info).context(format!(
-                    "parsing {} from JSON",
-                    std::any::type_name::<PlayerData>()
-                ))?;
+            let blurbs = blurbs(elem, |e| e.value().name() == PLAYER_SLOT);
+
+            for (slot, info) in infos.into_iter().enumerate().filter(|(_, v)| !v.is_null()) {
+                let info: PlayerData =
+                    json::from_value(info).map_err(|e| LayoutError::Invalid {
+                        source: format!("'{PLAYER_INFOS}' entry {slot}"),
+                        reason: e.to_string(),
+                    })?;
                 idx += 1;
-                if let Some(mut track) = info.get_track(idx) {
-                    track.bandcamp_playlist_track_number = idx;
-                    list.tracks.push(track.clone());
-                }
+
+                let blurb = blurbs.get(slot).cloned().flatten();
+                list.tracks
+                    .extend(
+                        info.get_tracks(idx, full_album)
+                            .into_iter()
+                            .map(|track| Track {
+                                blurb: blurb.clone(),
+                                ..track
+                            }),
+                    );
             }
         }
 
@@ -180,6 +316,79 @@ impl TrackList {
     }
 }
 
+/// A non-Bandcamp player embedded in a post
+#[derive(Debug, PartialEq, Eq, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub(crate) enum ExternalMedia {
+    #[serde(rename = "youtube")]
+    YouTube {
+        video_id: String,
+    },
+    #[serde(rename = "soundcloud")]
+    SoundCloud {
+        url: String,
+    },
+    Spotify {
+        item_type: String,
+        id: String,
+    },
+}
+
+impl ExternalMedia {
+    pub(crate) fn from_src(src: &str) -> Option<Self> {
+        // protocol-relative srcs are common in embed snippets
+        let src = match src.strip_prefix("//") {
+            Some(rest) => format!("https://{rest}"),
+            None => src.to_owned(),
+        };
+
+        let url = url::Url::parse(&src).ok()?;
+        let host = url.host_str()?.trim_start_matches("www.");
+        let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+
+        match (host, &segments[..]) {
+            ("youtube.com" | "youtube-nocookie.com", ["embed", id]) => Some(Self::YouTube {
+                video_id: id.to_string(),
+            }),
+            ("w.soundcloud.com", ["player"]) => url
+                .query_pairs()
+                .find(|(key, _)| key == "url")
+                .map(|(_, url)| Self::SoundCloud {
+                    url: url.into_owned(),
+                }),
+            ("open.spotify.com", ["embed", item_type, id]) => Some(Self::Spotify {
+                item_type: item_type.to_string(),
+                id: id.to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn spotify_track_id(&self) -> Option<&str> {
+        match self {
+            Self::Spotify { item_type, id } if item_type == "track" => Some(id),
+            _ => None,
+        }
+    }
+}
+
+/// Non-Bandcamp embeds within `root`, in document order.
+pub(crate) fn find_external_media(root: ElementRef) -> Vec<ExternalMedia> {
+    let mut media = vec![];
+
+    for found in root
+        .select(&IFRAME.selector)
+        .filter_map(|elem| elem.attr("src"))
+        .filter_map(ExternalMedia::from_src)
+    {
+        if !media.contains(&found) {
+            media.push(found);
+        }
+    }
+
+    media
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) struct BlogMeta {
     pub(crate) title: String,
@@ -187,27 +396,129 @@ pub(crate) struct BlogMeta {
     pub(crate) published: DateTime,
     pub(crate) modified: DateTime,
     pub(crate) description: String,
+    pub(crate) author: Option<String>,
+    pub(crate) series: Option<String>,
+    pub(crate) image: Option<String>,
+    pub(crate) tags: Vec<String>,
+    pub(crate) reading_minutes: Option<usize>,
+}
+
+fn meta_content(doc: &Html, selector: &HtmlSelector) -> Option<String> {
+    selector
+        .select(doc)
+        .find_map(|elem| elem.attr("content"))
+        .map(|res| res.to_owned())
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The writer, from the `<meta>` tags or the "By NAME · DATE" byline
+fn author(doc: &Html) -> Option<String> {
+    meta_content(doc, &META_AUTHOR)
+        .filter(|author| !author.starts_with("http"))
+        .or_else(|| {
+            let byline =
+                collapse_whitespace(&BYLINE.select(doc).next()?.text().collect::<String>());
+            let byline = byline.strip_prefix("By ").unwrap_or(&byline);
+            byline.split('·').next().map(str::trim).map(str::to_owned)
+        })
+        .filter(|author| !author.is_empty())
+}
+
+fn tags(doc: &Html) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+    for tag in META_TAG
+        .select(doc)
+        .filter_map(|elem| elem.attr("content"))
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+    {
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_owned());
+        }
+    }
+    tags
+}
+
+fn reading_minutes(root: ElementRef) -> Option<usize> {
+    let words = root
+        .text()
+        .map(|text| text.split_whitespace().count())
+        .sum::<usize>();
+
+    (words > 0).then(|| words.div_ceil(WORDS_PER_MINUTE))
 }
 
 impl BlogMeta {
     pub(crate) fn try_from_html(doc: &Html) -> anyhow::Result<Self> {
-        fn get_meta(doc: &Html, selector: &HtmlSelector) -> anyhow::Result<String> {
-            // TODO: memoize or lazy_static all of the properties we use
-            selector
-                .select(doc)
-                .find_map(|elem| elem.attr("content"))
-                .map(|res| res.to_owned())
-                .ok_or_else(|| {
-                    anyhow::format_err!("no metadata content found for '{}'", selector.text)
+        fn get_meta(doc: &Html, selector: &HtmlSelector) -> Result<String, LayoutError> {
+            meta_content(doc, selector)
+                .filter(|content| !content.is_empty())
+                .ok_or_else(|| LayoutError::MissingMeta(selector.text.clone()))
+        }
+
+        fn get_date(doc: &Html, selector: &HtmlSelector) -> Result<DateTime, LayoutError> {
+            get_meta(doc, selector)?
+                .parse()
+                .map_err(|e: chrono::ParseError| LayoutError::Invalid {
+                    source: format!("'{}'", selector.text),
+                    reason: e.to_string(),
                 })
         }
 
         Ok(Self {
             title: get_meta(doc, &META_TITLE)?,
             url: get_meta(doc, &META_URL)?,
-            published: get_meta(doc, &META_PUBLISHED)?.parse()?,
-            modified: get_meta(doc, &META_MODIFIED)?.parse()?,
+            published: get_date(doc, &META_PUBLISHED)?,
+            modified: get_date(doc, &META_MODIFIED)?,
             description: get_meta(doc, &META_DESCRIPTION)?,
+            author: author(doc),
+            series: meta_content(doc, &META_SECTION),
+            image: meta_content(doc, &META_IMAGE),
+            tags: tags(doc),
+            reading_minutes: DAILY_ARTICLE.select(doc).next().and_then(reading_minutes),
+        })
+    }
+
+    /// Best-effort metadata for pages that aren't Bandcamp Daily articles,
+    /// falling back to what the feed told us about the post. The publish
+    /// date names the post directory, so there must be one.
+    pub(crate) fn from_generic_html(
+        doc: &Html,
+        entry: &crate::feed::Entry,
+    ) -> Result<Self, LayoutError> {
+        let title = meta_content(doc, &META_TITLE)
+            .or_else(|| {
+                TITLE
+                    .select(doc)
+                    .map(|elem| elem.text().collect::<String>().trim().to_owned())
+                    .find(|title| !title.is_empty())
+            })
+            .or_else(|| entry.title.clone())
+            .unwrap_or_else(|| entry.url.clone());
+
+        let published = meta_content(doc, &META_PUBLISHED)
+            .and_then(|published| published.parse().ok())
+            .or(entry.published)
+            .ok_or_else(|| LayoutError::MissingMeta(META_PUBLISHED.text.clone()))?;
+
+        let modified = meta_content(doc, &META_MODIFIED)
+            .and_then(|modified| modified.parse().ok())
+            .unwrap_or(published);
+
+        Ok(Self {
+            title,
+            url: entry.url.clone(),
+            published,
+            modified,
+            description: meta_content(doc, &META_DESCRIPTION).unwrap_or_default(),
+            author: author(doc).or_else(|| entry.author.clone()),
+            series: meta_content(doc, &META_SECTION),
+            image: meta_content(doc, &META_IMAGE),
+            tags: tags(doc),
+            reading_minutes: None,
         })
     }
 }
@@ -221,6 +532,33 @@ pub(crate) struct BlogPost {
     pub(crate) description: String,
     pub(crate) tracks: Vec<Track>,
     pub(crate) raw: Vec<json::Value>,
+
+    #[serde(default)]
+    pub(crate) categories: Vec<String>,
+    #[serde(default)]
+    pub(crate) author: Option<String>,
+    #[serde(default)]
+    pub(crate) series: Option<String>,
+    #[serde(default)]
+    pub(crate) image: Option<String>,
+    #[serde(default)]
+    pub(crate) tags: Vec<String>,
+    #[serde(default)]
+    pub(crate) reading_minutes: Option<usize>,
+    #[serde(default)]
+    pub(crate) extractor: crate::source::Extractor,
+
+    #[serde(default)]
+    pub(crate) external_media: Vec<ExternalMedia>,
+
+    /// The page as fetched, archived alongside the post
+    #[serde(skip)]
+    pub(crate) html: Option<String>,
+
+    /// When the pages with the stream URLs were fetched, if just now or from
+    /// the cache
+    #[serde(skip)]
+    pub(crate) fetched: Option<DateTime>,
 }
 
 impl BlogPost {
@@ -231,6 +569,11 @@ impl BlogPost {
             published,
             modified,
             description,
+            author,
+            series,
+            image,
+            tags,
+            reading_minutes,
         } = meta;
 
         let TrackList { tracks, raw } = tracks;
@@ -243,28 +586,204 @@ impl BlogPost {
             description,
             tracks,
             raw,
+            categories: vec![],
+            author,
+            series,
+            image,
+            tags,
+            reading_minutes,
+            extractor: Default::default(),
+            external_media: vec![],
+            html: None,
+            fetched: None,
+        }
+    }
+
+    /// Fills in metadata that only the feed knows about.
+    pub(crate) fn apply_entry(&mut self, entry: &crate::feed::Entry) {
+        if !entry.categories.is_empty() {
+            self.categories = entry.categories.clone();
+        }
+
+        if entry.author.is_some() {
+            self.author = entry.author.clone();
         }
     }
 
-    pub(crate) fn from_html(html: &str) -> anyhow::Result<Self> {
+    /// Categories used for filtering: the feed categories plus the URL
+    /// section, which is all we have for posts that didn't come from the feed.
+    pub(crate) fn filter_categories(&self) -> Vec<String> {
+        let mut categories = self.categories.clone();
+        categories.extend(url_section(&self.url));
+        categories
+    }
+
+    pub(crate) fn from_html(html: &str, full_album: bool) -> anyhow::Result<Self> {
         let doc = Html::parse_document(html);
 
         let meta = BlogMeta::try_from_html(&doc).context("extracting blog metadata from HTML")?;
-        let tracks =
-            TrackList::try_from_html(&doc).context("extracting blog track list from HTML")?;
-        Ok(Self::new(meta, tracks))
+        let tracks = TrackList::try_from_html(&doc, full_album)
+            .context("extracting blog track list from HTML")?;
+
+        let mut post = Self::new(meta, tracks);
+        post.external_media = DAILY_ARTICLE
+            .select(&doc)
+            .flat_map(find_external_media)
+            .collect();
+        Ok(post)
+    }
+
+    pub(crate) async fn try_from_url(
+        url: &str,
+        client: &crate::http::Client,
+        full_album: bool,
+    ) -> anyhow::Result<Self> {
+        let (html, fetched) = client.fetch_text_dated(url).await?;
+        let mut post = Self::from_html(&html, full_album)?;
+        post.html = Some(html);
+        post.fetched = Some(fetched);
+        Ok(post)
+    }
+}
+
+pub(crate) fn archive_page_url(page: usize) -> String {
+    format!("{ARCHIVE_URL}?page={page}")
+}
+
+fn article_url(href: &str) -> Option<String> {
+    let path = match href.strip_prefix(BASE_URL) {
+        Some(path) => path,
+        None if href.starts_with('/') => href,
+        None => return None,
+    };
+
+    let path = path.split(['?', '#']).next()?;
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    match segments[..] {
+        [section, slug] if !NON_ARTICLE_SECTIONS.contains(&section) => {
+            Some(format!("{BASE_URL}/{section}/{slug}"))
+        }
+        _ => None,
+    }
+}
+
+/// The blog section ("album-of-the-day", "lists", ...) of a post URL
+pub(crate) fn url_section(url: &str) -> Option<String> {
+    let url = article_url(url)?;
+    let path = url.strip_prefix(BASE_URL)?;
+    path.split('/').find(|s| !s.is_empty()).map(str::to_owned)
+}
+
+/// Extracts blog post URLs from a page of the blog archive, in page order.
+pub(crate) fn archive_post_urls(html: &str) -> Vec<String> {
+    let doc = Html::parse_document(html);
+
+    let mut urls: Vec<String> = Vec::new();
+    for url in LINK
+        .select(&doc)
+        .filter_map(|elem| elem.attr("href"))
+        .filter_map(article_url)
+    {
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+
+    urls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Synthetic Bandcamp Daily pages, modelled on the real markup but
+    /// reduced to the parts the scraper reads and with made-up ids and URLs,
+    /// and the `BlogPost` extracted from each. Set
+    /// `BCDF_UPDATE_SNAPSHOTS=1` to rewrite the snapshots after an
+    /// intentional change.
+    const FIXTURES: &[&str] = &["single-album", "list", "null-players", "compilation"];
+
+    fn fixture_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/daily")
+    }
+
+    #[test]
+    fn blog_post_snapshots() {
+        let dir = fixture_dir();
+        let update = std::env::var_os("BCDF_UPDATE_SNAPSHOTS").is_some();
+
+        for name in FIXTURES {
+            let html = std::fs::read_to_string(dir.join(format!("{name}.html"))).unwrap();
+            let post = BlogPost::from_html(&html, false).unwrap();
+            let actual = json::to_value(&post).unwrap();
+
+            let path = dir.join(format!("{name}.json"));
+            if update {
+                let text = json::to_string_pretty(&actual).unwrap();
+                std::fs::write(&path, text + "\n").unwrap();
+                continue;
+            }
+
+            let expected: json::Value =
+                json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            assert_eq!(expected, actual, "snapshot mismatch for {name}");
+        }
+    }
+
+    #[test]
+    fn layout_errors() {
+        let html = std::fs::read_to_string(fixture_dir().join("single-album.html")).unwrap();
+
+        let layout_error = |html: &str| {
+            BlogPost::from_html(html, false)
+                .unwrap_err()
+                .downcast::<LayoutError>()
+                .unwrap()
+        };
+
+        assert_eq!(
+            LayoutError::MissingElement("#p-daily-article".to_string()),
+            layout_error(&html.replace("p-daily-article", "p-daily-story"))
+        );
+
+        assert!(matches!(
+            layout_error(&html.replace(r#"data-player-infos="["#, r#"data-player-infos="{"#)),
+            LayoutError::Invalid { .. }
+        ));
+
+        let post = BlogPost::from_html(&html.replace("data-player-infos", "data-players"), false);
+        assert!(post.unwrap().tracks.is_empty());
+
+        assert_eq!(
+            LayoutError::MissingMeta(r#"meta[property="og:url"]"#.to_string()),
+            layout_error(&html.replace(r#"property="og:url""#, r#"property="og:link""#))
+        );
     }
 
-    pub(crate) async fn try_from_url(url: &str, client: &reqwest::Client) -> anyhow::Result<Self> {
-        let req = client.get(url).build()?;
-        let bytes = client
-            .execute(req)
-            .await?
-            .error_for_status()?
-            .bytes()
-            .await?;
-
-        let html = String::from_utf8(bytes.to_vec())?;
-        Self::from_html(&html)
+    #[test]
+    fn blurbs_follow_players() {
+        let doc = Html::parse_document(
+            r#"<html><body>
+<p>Intro</p>
+<iframe src="https://bandcamp.com/EmbeddedPlayer/album=1/"></iframe>
+<p>First   paragraph.</p>
+<p>Second paragraph.</p>
+<iframe src="https://bandcamp.com/EmbeddedPlayer/album=2/"></iframe>
+<iframe src="https://bandcamp.com/EmbeddedPlayer/album=3/"></iframe>
+<div><p>Nested.</p></div>
+</body></html>"#,
+        );
+
+        let blurbs = blurbs(doc.root_element(), |elem| elem.value().name() == "iframe");
+        assert_eq!(
+            vec![
+                Some("First paragraph.\n\nSecond paragraph.".to_string()),
+                None,
+                Some("Nested.".to_string()),
+            ],
+            blurbs
+        );
     }
 }
@@ -0,0 +1,147 @@
+//! On-disk cache of fetched pages, revalidated with `ETag`/`Last-Modified`
+//! once older than the TTL.
+
+use crate::types::{DateTime, Duration};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+const CACHE_DIR: &str = ".cache/http";
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct Meta {
+    pub(crate) url: String,
+    pub(crate) etag: Option<String>,
+    pub(crate) last_modified: Option<String>,
+    pub(crate) fetched: DateTime,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Cached {
+    pub(crate) meta: Meta,
+    pub(crate) body: String,
+}
+
+impl Cached {
+    pub(crate) fn is_fresh(&self, ttl: Duration) -> bool {
+        (chrono::Utc::now() - self.meta.fetched)
+            .to_std()
+            .is_ok_and(|age| age < ttl)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Cache {
+    dir: PathBuf,
+    pub(crate) ttl: Duration,
+}
+
+fn dir(download_to: &str) -> PathBuf {
+    Path::new(download_to).join(CACHE_DIR)
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is stable between builds
+fn key(url: &str) -> String {
+    let hash = url.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+
+    format!("{hash:016x}")
+}
+
+impl Cache {
+    pub(crate) fn new(download_to: &str, ttl: Duration) -> Self {
+        Self {
+            dir: dir(download_to),
+            ttl,
+        }
+    }
+
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let key = key(url);
+        (
+            self.dir.join(format!("{key}.json")),
+            self.dir.join(format!("{key}.gz")),
+        )
+    }
+
+    pub(crate) fn get(&self, url: &str) -> Option<Cached> {
+        let (meta_path, body_path) = self.paths(url);
+
+        let meta: Meta = crate::state::load(&meta_path).ok()?;
+        if meta.url != url {
+            return None;
+        }
+
+        let mut body = String::new();
+        flate2::read::GzDecoder::new(std::fs::File::open(body_path).ok()?)
+            .read_to_string(&mut body)
+            .ok()?;
+
+        Some(Cached { meta, body })
+    }
+
+    pub(crate) fn put(&self, meta: &Meta, body: &str) -> anyhow::Result<()> {
+        let (meta_path, body_path) = self.paths(&meta.url);
+        std::fs::create_dir_all(&self.dir)?;
+
+        let mut gz = flate2::write::GzEncoder::new(
+            std::fs::File::create(body_path)?,
+            flate2::Compression::default(),
+        );
+        gz.write_all(body.as_bytes())?;
+        gz.finish()?;
+
+        crate::state::save(meta, &meta_path)
+    }
+
+    /// Marks a cached response as just revalidated.
+    pub(crate) fn touch(&self, meta: &Meta) -> anyhow::Result<()> {
+        let (meta_path, _) = self.paths(&meta.url);
+        crate::state::save(
+            &Meta {
+                fetched: chrono::Utc::now(),
+                ..meta.clone()
+            },
+            &meta_path,
+        )
+    }
+}
+
+/// Deletes every cached response, returning how many there were.
+pub(crate) fn clear(download_to: &str) -> anyhow::Result<usize> {
+    let dir = dir(download_to);
+    if !dir.is_dir() {
+        return Ok(0);
+    }
+
+    let count = std::fs::read_dir(&dir)?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .count();
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn freshness() {
+        assert_eq!("af63dc4c8601ec8c", key("a"));
+
+        let cached = |age| Cached {
+            meta: Meta {
+                url: "https://daily.bandcamp.com/".to_owned(),
+                etag: None,
+                last_modified: None,
+                fetched: chrono::Utc::now() - chrono::Duration::seconds(age),
+            },
+            body: String::new(),
+        };
+
+        assert!(cached(10).is_fresh(Duration::from_secs(60)));
+        assert!(!cached(120).is_fresh(Duration::from_secs(60)));
+    }
+}
@@ -1,8 +1,34 @@
-use clap::Parser;
+use crate::types::Duration;
+use clap::{CommandFactory, Parser, Subcommand};
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum CacheCommand {
+    /// Delete every cached page
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Command {
+    /// Manage the on-disk cache of fetched pages
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
+
+    /// Keep running, polling the feed for new posts on a schedule
+    Watch {
+        /// Time between polls (e.g. 30m, 1h, 1h30m)
+        #[arg(long, default_value = "1h", value_parser = crate::util::parse_duration)]
+        interval: Duration,
+    },
+}
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub(crate) struct Args {
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+
     /// Base directory for storing downloaded content
     #[arg(long, value_name = "PATH", default_value_t = crate::state::OUT_DIR.to_string())]
     pub(crate) download_to: String,
@@ -15,15 +41,199 @@ pub(crate) struct Args {
     #[arg(long, default_value_t = false)]
     pub(crate) no_spotify: bool,
 
-    /// Scan only a single url
+    /// Don't fetch release details from Bandcamp album/track pages
+    #[arg(long, default_value_t = false)]
+    pub(crate) no_enrich: bool,
+
+    /// Name for new Spotify playlists. Placeholders: {date}, {title},
+    /// {author}, {series}, {tags}
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        default_value = crate::spotify::DEFAULT_PLAYLIST_NAME,
+        value_parser = crate::spotify::parse_playlist_name
+    )]
+    pub(crate) playlist_name: String,
+
+    /// How long fetched post and album pages are used without checking
+    /// whether they've changed
+    #[arg(long, value_name = "DURATION", default_value = "1d", value_parser = crate::util::parse_duration)]
+    pub(crate) cache_ttl: Duration,
+
+    /// Don't read or write the page cache
+    #[arg(long, default_value_t = false)]
+    pub(crate) no_cache: bool,
+
+    /// User-Agent header to send with every Bandcamp request
+    #[arg(long, value_name = "UA", default_value = crate::http::DEFAULT_USER_AGENT)]
+    pub(crate) user_agent: String,
+
+    /// Send every Bandcamp request through this HTTP, HTTPS or SOCKS proxy.
+    /// Spotify requests use the HTTPS_PROXY environment variable instead
+    #[arg(long, value_name = "URL")]
+    pub(crate) proxy: Option<url::Url>,
+
+    /// Extra header to send with every Bandcamp request, as "Name: value".
+    /// May be given more than once
+    #[arg(long = "header", value_name = "HEADER", value_parser = crate::http::parse_header)]
+    pub(crate) headers: Vec<(reqwest::header::HeaderName, reqwest::header::HeaderValue)>,
+
+    /// Load cookies from a Netscape-format cookies.txt file, e.g. to send a
+    /// Bandcamp session
+    #[arg(long, value_name = "PATH")]
+    pub(crate) cookies: Option<std::path::PathBuf>,
+
+    /// Maximum number of concurrent requests to any one host
+    #[arg(long, value_name = "N", default_value_t = crate::http::DEFAULT_MAX_PER_HOST)]
+    pub(crate) max_per_host: usize,
+
+    /// Maximum number of requests per second to any one host (0 for no limit)
+    #[arg(
+        long,
+        value_name = "RPS",
+        default_value_t = crate::http::DEFAULT_REQUESTS_PER_SECOND,
+        value_parser = crate::http::parse_requests_per_second
+    )]
+    pub(crate) requests_per_second: f64,
+
+    /// Number of tracks to download at once, across all posts
+    #[arg(long, value_name = "N", default_value_t = crate::download::DEFAULT_WORKERS)]
+    pub(crate) download_workers: usize,
+
+    /// Maximum width/height in pixels of downloaded cover art
+    #[arg(long, value_name = "PIXELS", default_value_t = crate::cover::DEFAULT_MAX_SIZE)]
+    pub(crate) cover_size: u32,
+
+    /// Scan only a single url: a blog post, a Bandcamp album, track, artist
+    /// or label page, or a fan's collection or wishlist
     #[arg(long)]
     pub(crate) url: Option<String>,
 
     /// Re-scan from the filesystem only
     #[arg(long, default_value_t = false)]
     pub(crate) rescan: bool,
+
+    /// Rebuild saved posts from their archived HTML instead of fetching them.
+    /// Nothing is downloaded and Spotify is left alone; tags are rewritten
+    #[arg(long, default_value_t = false, conflicts_with_all = ["url", "backfill", "feeds", "opml"])]
+    pub(crate) offline: bool,
+
+    /// Walk the blog archive back to DATE (YYYY-MM-DD), resuming any
+    /// interrupted backfill
+    #[arg(long, value_name = "DATE", conflicts_with_all = ["url", "rescan"])]
+    pub(crate) backfill: Option<chrono::NaiveDate>,
+
+    /// Scan this RSS/Atom feed instead of Bandcamp Daily, as URL or
+    /// EXTRACTOR:URL (extractors: bandcamp-daily, embedded-player). May be
+    /// given more than once
+    #[arg(long = "feed", value_name = "FEED")]
+    pub(crate) feeds: Vec<crate::source::Source>,
+
+    /// Scan every feed listed in an OPML file
+    #[arg(long, value_name = "PATH")]
+    pub(crate) opml: Option<String>,
+
+    /// Process posts even if they are already complete, and ignore the saved
+    /// feed position
+    #[arg(long, default_value_t = false)]
+    pub(crate) force: bool,
+
+    /// Only process posts published on or after DATE (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE")]
+    pub(crate) since: Option<chrono::NaiveDate>,
+
+    /// Only process posts published on or before DATE (YYYY-MM-DD)
+    #[arg(long, value_name = "DATE")]
+    pub(crate) until: Option<chrono::NaiveDate>,
+
+    /// Capture every track of each embedded release instead of only the
+    /// featured one
+    #[arg(long, default_value_t = false)]
+    pub(crate) full_album: bool,
+
+    /// Like --full-album, but only for posts in CATEGORY. May be given more
+    /// than once
+    #[arg(long = "full-album-category", value_name = "CATEGORY")]
+    pub(crate) full_album_categories: Vec<String>,
+
+    /// Only process posts in CATEGORY (e.g. "Album of the Day"). May be
+    /// given more than once
+    #[arg(long = "category", value_name = "CATEGORY")]
+    pub(crate) categories: Vec<String>,
+
+    /// Skip posts in CATEGORY. May be given more than once
+    #[arg(long = "exclude-category", value_name = "CATEGORY")]
+    pub(crate) exclude_categories: Vec<String>,
+}
+
+impl Args {
+    /// Checks a publish date against `--since`/`--until`.
+    pub(crate) fn in_date_range(&self, published: &crate::types::DateTime) -> bool {
+        let date = published.date_naive();
+        self.since.is_none_or(|since| date >= since) && self.until.is_none_or(|until| date <= until)
+    }
+
+    /// Checks a post's categories against `--category`/`--exclude-category`.
+    pub(crate) fn wants_categories(&self, categories: &[String]) -> bool {
+        if !self.categories.is_empty() && !any_category(&self.categories, categories) {
+            return false;
+        }
+
+        !any_category(&self.exclude_categories, categories)
+    }
+
+    pub(crate) fn http_limits(&self) -> crate::http::Limits {
+        crate::http::Limits {
+            max_per_host: self.max_per_host,
+            requests_per_second: self.requests_per_second,
+        }
+    }
+
+    pub(crate) fn http_identity(&self) -> crate::http::Identity {
+        crate::http::Identity {
+            user_agent: self.user_agent.clone(),
+            proxy: self.proxy.clone(),
+            headers: self.headers.clone(),
+            cookies: self.cookies.clone(),
+        }
+    }
+
+    pub(crate) fn wants_full_album(&self, categories: &[String]) -> bool {
+        self.full_album || any_category(&self.full_album_categories, categories)
+    }
+}
+
+fn any_category(filter: &[String], categories: &[String]) -> bool {
+    use crate::util::slugify;
+
+    filter
+        .iter()
+        .map(|f| slugify(f))
+        .any(|f| categories.iter().any(|c| slugify(c) == f))
 }
 
 pub(crate) fn args() -> Args {
-    Args::parse()
+    let args = Args::parse();
+
+    // clap can't express a conflict between an argument and a subcommand
+    if args.command.is_some() && (args.backfill.is_some() || args.offline) {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--backfill and --offline can't be used with a subcommand",
+            )
+            .exit();
+    }
+
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args_are_consistent() {
+        Args::command().debug_assert();
+    }
 }
@@ -0,0 +1,143 @@
+//! Album artwork, downloaded once per release and embedded in the MP3 tags.
+
+use crate::state::State;
+use crate::types::{Album, Track};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+const ART_URL: &str = "https://f4.bcbits.com/img";
+pub(crate) const COVER_FILENAME: &str = "cover.jpg";
+pub(crate) const DEFAULT_MAX_SIZE: u32 = 1200;
+
+/// Square image sizes served by bcbits, as (pixels, format code)
+const SIZES: &[(u32, u32)] = &[
+    (1200, 10),
+    (700, 16),
+    (350, 2),
+    (210, 9),
+    (150, 7),
+    (100, 3),
+];
+
+/// Extracts the art id from an image URL like
+/// `https://f4.bcbits.com/img/a0123456789_10.jpg`
+fn art_id_from_url(url: &str) -> Option<u64> {
+    let (_, name) = url.rsplit_once("/a")?;
+    let (id, _) = name.split_once('_')?;
+    id.parse().ok()
+}
+
+fn art_id(album: &Album) -> Option<u64> {
+    album.art_id.or_else(|| {
+        album
+            .details
+            .as_ref()?
+            .artwork_url
+            .as_deref()
+            .and_then(art_id_from_url)
+    })
+}
+
+/// The largest available image that is no bigger than `max_size` pixels.
+pub(crate) fn art_url(art_id: u64, max_size: u32) -> String {
+    let code = SIZES
+        .iter()
+        .find(|(size, _)| *size <= max_size)
+        .or(SIZES.last())
+        .map(|(_, code)| *code)
+        .expect("unreachable!");
+
+    format!("{ART_URL}/a{art_id:010}_{code}.jpg")
+}
+
+/// Where the cover for a track's release lives: alongside the tracks for
+/// full-album releases and single-release posts, otherwise one file per
+/// release in the post directory.
+pub(crate) fn path(state: &State, track: &Track) -> PathBuf {
+    let dir = state.dirname();
+
+    if track.full_album {
+        if let Some(album_dir) = track.mp3_filename().parent() {
+            return dir.join(album_dir).join(COVER_FILENAME);
+        }
+    }
+
+    let single = state
+        .tracks
+        .iter()
+        .all(|other| other.album.title == track.album.title);
+
+    if single {
+        dir.join(COVER_FILENAME)
+    } else {
+        let album_artist = track.album_artist.name.replace('/', "_");
+        let album = track.album.title.replace('/', "_");
+        dir.join(format!("{album_artist} - {album} - {COVER_FILENAME}"))
+    }
+}
+
+pub(crate) async fn download(state: &State, client: &crate::http::Client, max_size: u32) {
+    let mut seen = HashSet::new();
+
+    for track in &state.tracks {
+        let path = path(state, track);
+        if !seen.insert(path.clone()) || path.is_file() {
+            continue;
+        }
+
+        let Some(art_id) = art_id(&track.album) else {
+            tracing::debug!(album = track.album.title, "SKIP: no cover art");
+            continue;
+        };
+
+        let url = art_url(art_id, max_size);
+        if let Err(e) = fetch(client, &url, &path).await {
+            tracing::error!(url, ?path, "failed downloading cover art: {e}");
+        }
+    }
+}
+
+async fn fetch(client: &crate::http::Client, url: &str, path: &PathBuf) -> anyhow::Result<()> {
+    let req = client.get(url).build()?;
+    let bytes = client
+        .execute(req)
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+
+    tokio::fs::write(path, &bytes).await?;
+    tracing::debug!(?path, "downloaded cover art");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cover_art_url() {
+        assert_eq!(
+            Some(123456789),
+            art_id_from_url("https://f4.bcbits.com/img/a0123456789_10.jpg")
+        );
+
+        assert_eq!(
+            "https://f4.bcbits.com/img/a0123456789_10.jpg",
+            art_url(123456789, 1200)
+        );
+        assert_eq!(
+            "https://f4.bcbits.com/img/a0123456789_16.jpg",
+            art_url(123456789, 1000)
+        );
+        assert_eq!(
+            "https://f4.bcbits.com/img/a0123456789_3.jpg",
+            art_url(123456789, 10)
+        );
+    }
+}
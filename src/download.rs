@@ -1,70 +1,581 @@
-use crate::http;
 use crate::metrics;
+use crate::progress::Progress;
+use crate::release::Release;
+use crate::state::State;
+use crate::types::{DateTime, DownloadFailures, Duration, Track};
 use futures::stream::StreamExt;
+use reqwest::header::{CONTENT_RANGE, CONTENT_TYPE, RANGE};
+use reqwest::StatusCode;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, OnceLock};
 use tokio::io::AsyncWriteExt;
-use tokio::task::JoinSet;
+use tokio::sync::{mpsc, oneshot, Mutex};
 
-pub(crate) async fn download(state: &crate::state::State) {
-    let mut set: JoinSet<anyhow::Result<()>> = JoinSet::new();
+pub(crate) const DEFAULT_WORKERS: usize = 4;
 
-    let client = http::client();
+const PART_EXTENSION: &str = "part";
 
-    for track in &state.tracks {
-        let track = track.clone();
+/// Signed stream URLs older than this are refreshed before downloading
+const MAX_URL_AGE: Duration = Duration::from_secs(6 * 60 * 60);
 
+/// Delay before retrying a failed download, doubled with each failure
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(60 * 60);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Tracks that have failed this many times are reported as permanently
+/// failing, though still retried every so often
+const PERMANENT_FAILURE_ATTEMPTS: u32 = 5;
+
+/// Downloads that fail validation are moved here, under the post directory
+const QUARANTINE_DIR: &str = ".quarantine";
+
+/// Partial downloads left alone for longer than this are deleted on startup
+/// rather than resumed
+const STALE_PART_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Debug)]
+struct Job {
+    title: String,
+    url: String,
+    path: PathBuf,
+    quarantine: PathBuf,
+    duration: Duration,
+    done: oneshot::Sender<anyhow::Result<()>>,
+}
+
+/// The server refused a stream URL in a way that suggests its signature
+/// has expired
+#[derive(Debug)]
+struct ExpiredUrl(StatusCode);
+
+impl std::fmt::Display for ExpiredUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "download url has expired ({})", self.0)
+    }
+}
+
+impl std::error::Error for ExpiredUrl {}
+
+#[derive(Debug)]
+struct BadStatus(StatusCode);
+
+impl std::fmt::Display for BadStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "non-200 status: {}", self.0)
+    }
+}
+
+impl std::error::Error for BadStatus {}
+
+fn status(error: &anyhow::Error) -> Option<u16> {
+    error
+        .downcast_ref::<ExpiredUrl>()
+        .map(|e| e.0)
+        .or_else(|| error.downcast_ref::<BadStatus>().map(|e| e.0))
+        .or_else(|| error.downcast_ref::<reqwest::Error>()?.status())
+        .map(|status| status.as_u16())
+}
+
+/// Permanently failing tracks seen during this run, keyed by path
+static FAILING: LazyLock<std::sync::Mutex<BTreeMap<PathBuf, DownloadFailures>>> =
+    LazyLock::new(Default::default);
+
+fn note_failing(path: PathBuf, failures: Option<&DownloadFailures>) {
+    let mut failing = FAILING.lock().expect("failing downloads lock is poisoned!");
+
+    match failures {
+        Some(failures) if failures.attempts >= PERMANENT_FAILURE_ATTEMPTS => {
+            failing.insert(path, failures.clone());
+        }
+        _ => {
+            failing.remove(&path);
+        }
+    }
+}
+
+/// Tracks that keep failing to download, for the end-of-run summary.
+pub(crate) fn failing() -> BTreeMap<PathBuf, DownloadFailures> {
+    FAILING
+        .lock()
+        .expect("failing downloads lock is poisoned!")
+        .clone()
+}
+
+fn retry_delay(attempts: u32) -> Duration {
+    RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(RETRY_MAX_DELAY)
+}
+
+fn record_failure(track: &mut Track, error: &anyhow::Error, now: DateTime) {
+    let failures = track.download_failures.get_or_insert_with(Default::default);
+
+    failures.attempts += 1;
+    failures.last_error = Some(format!("{error:#}"));
+    failures.last_status = status(error);
+    failures.next_retry = chrono::Duration::from_std(retry_delay(failures.attempts))
+        .ok()
+        .map(|delay| now + delay);
+}
+
+/// Track downloads from every post, shared by a fixed number of workers.
+#[derive(Debug)]
+struct Queue {
+    jobs: mpsc::UnboundedSender<Job>,
+    progress: Progress,
+    client: crate::http::Client,
+}
+
+static QUEUE: OnceLock<Queue> = OnceLock::new();
+
+/// Starts the download workers. Must be called before [`download`].
+pub(crate) fn start(client: &crate::http::Client, workers: usize) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let rx = Arc::new(Mutex::new(rx));
+
+    let progress = Progress::default();
+    progress.spawn();
+
+    for _ in 0..workers.max(1) {
+        let rx = rx.clone();
+        let client = client.clone();
+        let progress = progress.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let Some(job) = rx.lock().await.recv().await else {
+                    break;
+                };
+
+                let res = fetch(&client, &progress, &job).await;
+                let _ = job.done.send(res);
+            }
+        });
+    }
+
+    let queue = Queue {
+        jobs: tx,
+        progress,
+        client: client.clone(),
+    };
+
+    if QUEUE.set(queue).is_err() {
+        panic!("download workers were started twice!");
+    }
+}
+
+async fn fetch(client: &crate::http::Client, progress: &Progress, job: &Job) -> anyhow::Result<()> {
+    let Job {
+        title,
+        url,
+        path,
+        quarantine,
+        duration,
+        ..
+    } = job;
+
+    tracing::info!(title, "downloading");
+    let file = progress.start(title);
+
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+
+    let part = part_path(path);
+    let mut offset = match tokio::fs::metadata(&part).await {
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    };
+
+    let res = loop {
+        let mut req = client.get(url);
+        if offset > 0 {
+            req = req.header(RANGE, format!("bytes={offset}-"));
+        }
+
+        let res = client.execute(req.build()?).await?;
+
+        match res.status() {
+            StatusCode::OK => {
+                offset = 0;
+                break res;
+            }
+            StatusCode::PARTIAL_CONTENT if content_range_start(&res) == Some(offset) => {
+                tracing::debug!(title, offset, "resuming download");
+                break res;
+            }
+            // the partial is no good to us, so start over
+            StatusCode::PARTIAL_CONTENT | StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => {
+                tracing::debug!(title, offset, "server refused to resume, restarting");
+                tokio::fs::remove_file(&part).await?;
+                offset = 0;
+            }
+            status @ (StatusCode::FORBIDDEN | StatusCode::GONE) => {
+                return Err(ExpiredUrl(status).into());
+            }
+            status => {
+                let body = res.text().await.ok();
+                tracing::error!(title, status = status.as_u16(), body, "download failed");
+
+                return Err(BadStatus(status).into());
+            }
+        }
+    };
+
+    file.add(offset);
+    file.set_total(res.content_length().map(|len| len + offset));
+
+    let content_type = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned);
+
+    let mut fh = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(offset > 0)
+        .truncate(offset == 0)
+        .open(&part)
+        .await?;
+
+    let mut bytes = res.bytes_stream();
+    while let Some(bytes) = bytes.next().await {
+        let bytes = bytes?;
+        fh.write_all(bytes.as_ref()).await?;
+        file.add(bytes.len() as u64);
+    }
+
+    fh.sync_all().await?;
+    drop(fh);
+
+    let data = tokio::fs::read(&part).await?;
+    if let Err(invalid) = crate::audio::validate(&data, content_type.as_deref(), *duration) {
+        tracing::warn!(title, "quarantining download: {invalid}");
+        metrics::inc(metrics::TracksQuarantined, 1);
+
+        if let Some(dir) = quarantine.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        tokio::fs::rename(&part, quarantine).await?;
+
+        return Err(invalid.into());
+    }
+
+    tokio::fs::rename(&part, path).await?;
+    if let Some(dir) = path.parent() {
+        tokio::fs::File::open(dir).await?.sync_all().await?;
+    }
+
+    file.finish();
+    tracing::debug!(title, "finished downloading");
+    metrics::inc(metrics::TracksDownloaded, 1);
+    Ok(())
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".");
+    part.push(PART_EXTENSION);
+    PathBuf::from(part)
+}
+
+/// Parses the first byte position out of `Content-Range: bytes 100-999/1000`.
+fn content_range_start(res: &crate::http::Response) -> Option<u64> {
+    let value = res.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    parse_content_range_start(value)
+}
+
+fn parse_content_range_start(value: &str) -> Option<u64> {
+    let (start, _) = value.strip_prefix("bytes ")?.split_once('-')?;
+    start.trim().parse().ok()
+}
+
+/// Deletes partial downloads too old to be worth resuming, returning how
+/// many there were.
+pub(crate) fn clean_stale_parts(download_to: &str) -> anyhow::Result<usize> {
+    let mut removed = 0;
+    let mut dirs = vec![PathBuf::from(download_to)];
+
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            let meta = entry.metadata()?;
+
+            if meta.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+
+            if path.extension().is_none_or(|ext| ext != PART_EXTENSION) {
+                continue;
+            }
+
+            let age = meta.modified()?.elapsed().unwrap_or_default();
+            if age > STALE_PART_AGE {
+                tracing::debug!(path = %path.display(), "removing stale partial download");
+                std::fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Queues downloads for the given tracks and waits for them to finish.
+async fn run(queue: &Queue, state: &State, tracks: &[usize]) -> Vec<(usize, anyhow::Result<()>)> {
+    let mut pending = Vec::new();
+
+    for &i in tracks {
+        let track = &state.tracks[i];
         let Some(url) = track.download_url.clone() else {
-            tracing::debug!(track.title, "SKIP: no download url");
             continue;
         };
 
-        let path = state.dirname().join(track.mp3_filename());
+        let (done, rx) = oneshot::channel();
+        let job = Job {
+            title: track.title.clone(),
+            url,
+            path: state.dirname().join(track.mp3_filename()),
+            quarantine: state
+                .dirname()
+                .join(QUARANTINE_DIR)
+                .join(track.mp3_filename()),
+            duration: track.duration,
+            done,
+        };
+
+        queue.progress.queued();
+        if queue.jobs.send(job).is_err() {
+            tracing::error!(track.title, "download workers have gone away");
+            continue;
+        }
+
+        pending.push((i, rx));
+    }
+
+    let mut results = Vec::with_capacity(pending.len());
+    for (i, rx) in pending {
+        let res = rx
+            .await
+            .unwrap_or_else(|_| Err(anyhow::format_err!("download was dropped")));
+        results.push((i, res));
+    }
+
+    results
+}
+
+/// Replaces a track's stream URL with a fresh one from its release page.
+async fn refresh_url(
+    track: &mut Track,
+    releases: &mut HashMap<String, Release>,
+    client: &crate::http::Client,
+) -> anyhow::Result<()> {
+    let Some(url) = track.album.bandcamp_url.clone() else {
+        anyhow::bail!("no release page to refresh the download url from");
+    };
+
+    let release = match releases.entry(url) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => {
+            let (html, _) = client.fetch_text_fresh(entry.key()).await?;
+            let release = Release::from_html(&html, entry.key())?;
+            entry.insert(release)
+        }
+    };
+
+    let tracklist = &release.player_data.tracklist;
+    let info = tracklist
+        .iter()
+        .find(|ti| {
+            ti.track_id.is_some() && ti.track_id.map(|id| id.to_string()) == track.bandcamp_track_id
+        })
+        .or_else(|| tracklist.iter().find(|ti| ti.track_title == track.title))
+        .ok_or_else(|| anyhow::format_err!("track not found on its release page"))?;
+
+    let Some(url) = info.download_url() else {
+        anyhow::bail!("release page has no download url for the track");
+    };
+
+    tracing::debug!(track.title, "refreshed download url");
+    metrics::inc(metrics::DownloadUrlsRefreshed, 1);
+
+    track.download_url = Some(url);
+    track.download_url_fetched = Some(chrono::Utc::now());
+    Ok(())
+}
+
+/// URLs of unknown age count as stale.
+fn is_stale(track: &Track) -> bool {
+    track.download_url_fetched.is_none_or(|fetched| {
+        (chrono::Utc::now() - fetched)
+            .to_std()
+            .is_ok_and(|age| age > MAX_URL_AGE)
+    })
+}
+
+/// Queues a post's missing tracks and waits for them to finish. Stream URLs
+/// that are old or turn out to have expired are refreshed along the way.
+/// Failures are recorded on each track, which then isn't tried again until
+/// its backoff has passed.
+pub(crate) async fn download(state: &mut State) {
+    let queue = QUEUE.get().expect("download workers haven't been started!");
+    let mut releases = HashMap::new();
+    let mut wanted = Vec::new();
+    let now = chrono::Utc::now();
+
+    for (i, track) in state.tracks.iter().enumerate() {
+        if track.download_url.is_none() {
+            tracing::debug!(track.title, "SKIP: no download url");
+            continue;
+        }
 
+        let path = state.dirname().join(track.mp3_filename());
         if path.is_file() {
             tracing::debug!(track.title, "SKIP: exists");
             continue;
         }
 
-        let client = client.clone();
+        if let Some(failures) = &track.download_failures {
+            if !failures.is_due(now) {
+                tracing::debug!(
+                    track.title,
+                    failures.attempts,
+                    next_retry = ?failures.next_retry,
+                    "SKIP: backing off after failed downloads"
+                );
+                note_failing(path, Some(failures));
+                continue;
+            }
+        }
 
-        set.spawn(async move {
-            tracing::info!(track.title, "downloading");
+        wanted.push(i);
+    }
 
-            let req = client.get(url).build()?;
-            let res = client.execute(req).await?;
+    for &i in &wanted {
+        let track = &mut state.tracks[i];
+        if !is_stale(track) {
+            continue;
+        }
 
-            match res.status().as_u16() {
-                200 => {}
-                status => {
-                    let body = res.text().await.ok();
-                    tracing::error!(track.title, status, body, "download failed");
+        match refresh_url(track, &mut releases, &queue.client).await {
+            Ok(()) => state.need_save_tracks(),
+            Err(e) => tracing::warn!(track.title, "failed refreshing old download url: {e}"),
+        }
+    }
 
-                    anyhow::bail!("non-200 status: {status}");
-                }
-            }
+    let mut results = run(queue, state, &wanted).await;
 
-            let mut fh = tokio::fs::File::create(path.clone()).await?;
-            let mut bytes = res.bytes_stream();
-            while let Some(bytes) = bytes.next().await {
-                let bytes = bytes?;
-                fh.write_all(bytes.as_ref()).await?;
-            }
+    let mut expired = Vec::new();
+    for (i, res) in &results {
+        let Err(e) = res else {
+            continue;
+        };
 
-            tracing::debug!(track.title, "finished downloading");
-            metrics::inc(metrics::TracksDownloaded, 1);
-            Ok(())
-        });
+        if e.downcast_ref::<ExpiredUrl>().is_none() {
+            continue;
+        }
+
+        let track = &mut state.tracks[*i];
+        tracing::info!(track.title, "download url has expired, refreshing");
+
+        match refresh_url(track, &mut releases, &queue.client).await {
+            Ok(()) => expired.push(*i),
+            Err(e) => tracing::warn!(track.title, "failed refreshing expired download url: {e}"),
+        }
     }
 
-    while let Some(res) = set.join_next().await {
+    if !expired.is_empty() {
+        state.need_save_tracks();
+        results.retain(|(i, _)| !expired.contains(i));
+        results.extend(run(queue, state, &expired).await);
+    }
+
+    let now = chrono::Utc::now();
+    for (i, res) in results {
+        let path = state.dirname().join(state.tracks[i].mp3_filename());
+        let track = &mut state.tracks[i];
+
         match res {
-            Ok(join_res) => {
-                if let Err(error) = join_res {
-                    tracing::error!(?error, "download failed");
+            Ok(()) => {
+                if track.invalid_download.is_none() && track.download_failures.is_none() {
+                    continue;
                 }
+
+                track.invalid_download = None;
+                track.download_failures = None;
             }
             Err(error) => {
-                tracing::error!(?error, "download failed");
+                let invalid = error.downcast_ref::<crate::audio::InvalidAudio>();
+                if invalid.is_none() {
+                    tracing::error!(track.title, ?error, "download failed");
+                }
+
+                track.invalid_download = invalid.map(ToString::to_string);
+                record_failure(track, &error, now);
             }
         }
+
+        note_failing(path, track.download_failures.as_ref());
+        state.need_save_tracks();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partials() {
+        assert_eq!(
+            PathBuf::from("out/post/01 - Track.mp3.part"),
+            part_path(Path::new("out/post/01 - Track.mp3"))
+        );
+
+        assert_eq!(Some(100), parse_content_range_start("bytes 100-999/1000"));
+        assert_eq!(Some(0), parse_content_range_start("bytes 0-999/*"));
+        assert_eq!(None, parse_content_range_start("bytes */1000"));
+    }
+
+    #[test]
+    fn failure_backoff() {
+        assert_eq!(RETRY_BASE_DELAY, retry_delay(1));
+        assert_eq!(RETRY_BASE_DELAY * 4, retry_delay(3));
+        assert_eq!(RETRY_MAX_DELAY, retry_delay(30));
+
+        let now = "2024-01-05T00:00:00Z".parse().unwrap();
+        let mut track = Track::new("Title", "Artist", "Album");
+
+        record_failure(&mut track, &BadStatus(StatusCode::NOT_FOUND).into(), now);
+        record_failure(&mut track, &BadStatus(StatusCode::NOT_FOUND).into(), now);
+
+        let failures = track.download_failures.unwrap();
+        assert_eq!(2, failures.attempts);
+        assert_eq!(Some(404), failures.last_status);
+        assert_eq!(
+            Some("non-200 status: 404 Not Found"),
+            failures.last_error.as_deref()
+        );
+        assert!(!failures.is_due(now + chrono::Duration::hours(1)));
+        assert!(failures.is_due(now + chrono::Duration::hours(2)));
+    }
+
+    #[test]
+    fn url_age() {
+        let mut track = Track::new("Title", "Artist", "Album");
+        assert!(is_stale(&track));
+
+        track.download_url_fetched = Some(chrono::Utc::now() - chrono::Duration::hours(1));
+        assert!(!is_stale(&track));
+
+        track.download_url_fetched = Some(chrono::Utc::now() - chrono::Duration::hours(7));
+        assert!(is_stale(&track));
     }
 }
@@ -0,0 +1,266 @@
+use crate::bandcamp::{
+    self, selector, BlogMeta, BlogPost, PlayerData, TrackInfo, TrackList, IFRAME,
+};
+use crate::feed::Entry;
+use crate::types::{DateTime, Duration, Track};
+use anyhow::Context;
+use scraper::Html;
+use serde_json as json;
+use std::collections::BTreeMap;
+
+const PLAYER_URL: &str = "https://bandcamp.com/EmbeddedPlayer";
+
+selector!(PLAYER_DATA, "[data-player-data]");
+
+/// A `bandcamp.com/EmbeddedPlayer` iframe
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) struct Embed {
+    pub(crate) album_id: Option<u64>,
+    pub(crate) track_id: Option<u64>,
+}
+
+impl Embed {
+    /// Parses the player options from an iframe src like
+    /// `https://bandcamp.com/EmbeddedPlayer/album=123/size=large/track=456/`
+    pub(crate) fn from_src(src: &str) -> Option<Self> {
+        let (_, opts) = src.split_once("bandcamp.com/EmbeddedPlayer")?;
+
+        let mut embed = Self {
+            album_id: None,
+            track_id: None,
+        };
+
+        for (key, value) in opts
+            .split(['/', '?', '&', '#'])
+            .filter_map(|opt| opt.split_once('='))
+        {
+            match key {
+                "album" => embed.album_id = value.parse().ok(),
+                "track" => embed.track_id = value.parse().ok(),
+                _ => {}
+            }
+        }
+
+        if embed.album_id.is_none() && embed.track_id.is_none() {
+            return None;
+        }
+
+        Some(embed)
+    }
+
+    fn player_url(&self) -> String {
+        let mut url = PLAYER_URL.to_string();
+        if let Some(id) = self.album_id {
+            url.push_str(&format!("/album={id}"));
+        }
+        if let Some(id) = self.track_id {
+            url.push_str(&format!("/track={id}"));
+        }
+        url.push('/');
+        url
+    }
+
+    /// The player's data, and when it was fetched. Its stream URLs expire,
+    /// so it's always fetched anew.
+    pub(crate) async fn player_data(
+        &self,
+        client: &crate::http::Client,
+    ) -> anyhow::Result<(json::Value, DateTime)> {
+        let (html, fetched) = client.fetch_text_fresh(&self.player_url()).await?;
+
+        let doc = Html::parse_document(&html);
+        let data = PLAYER_DATA
+            .select(&doc)
+            .find_map(|elem| elem.attr("data-player-data"))
+            .ok_or_else(|| anyhow::format_err!("no player data found in embedded player"))?;
+
+        let data = json::from_str(data).context("parsing 'data-player-data' as JSON")?;
+        Ok((data, fetched))
+    }
+}
+
+pub(crate) fn find_embeds(doc: &Html) -> Vec<Embed> {
+    let mut embeds = Vec::new();
+
+    for embed in IFRAME
+        .select(doc)
+        .filter_map(|elem| elem.attr("src"))
+        .filter_map(Embed::from_src)
+    {
+        if !embeds.contains(&embed) {
+            embeds.push(embed);
+        }
+    }
+
+    embeds
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct EmbedTrack {
+    id: Option<u64>,
+    title: String,
+    artist: Option<String>,
+    tracknum: usize,
+    #[serde(deserialize_with = "crate::util::duration_from_f64")]
+    duration: Duration,
+    #[serde(default)]
+    file: Option<BTreeMap<String, String>>,
+}
+
+/// The `data-player-data` blob from the embedded player page
+#[derive(Debug, serde::Deserialize)]
+struct EmbedData {
+    album_id: Option<u64>,
+    album_title: Option<String>,
+    artist: String,
+    band_id: Option<u64>,
+    band_url: Option<String>,
+    linkback: Option<String>,
+    tracks: Vec<EmbedTrack>,
+    #[serde(default)]
+    art_id: Option<u64>,
+}
+
+impl EmbedData {
+    fn into_player_data(self, embed: &Embed) -> PlayerData {
+        let featured_track_number = embed
+            .track_id
+            .and_then(|id| self.tracks.iter().find(|t| t.id == Some(id)))
+            .or(self.tracks.first())
+            .map(|t| t.tracknum)
+            .unwrap_or(1);
+
+        let tracklist = self
+            .tracks
+            .into_iter()
+            .map(|t| TrackInfo {
+                artist: t.artist.unwrap_or_else(|| self.artist.clone()),
+                audio_track_duration: t.duration,
+                track_number: t.tracknum,
+                track_title: t.title,
+                audio_url: t.file.unwrap_or_default(),
+                album_id: self.album_id,
+                track_id: t.id,
+            })
+            .collect();
+
+        PlayerData {
+            title: self.album_title.unwrap_or_else(|| self.artist.clone()),
+            tracklist,
+            featured_track_number,
+            band_name: self.artist,
+            band_id: self.band_id,
+            band_location: None,
+            band_url: self.band_url,
+            tralbum_url: self.linkback,
+            art_id: self.art_id,
+        }
+    }
+}
+
+/// Builds a [`BlogPost`] from an arbitrary page with embedded Bandcamp players.
+pub(crate) async fn blog_post(
+    entry: &Entry,
+    client: &crate::http::Client,
+    full_album: bool,
+) -> anyhow::Result<BlogPost> {
+    let (html, _) = client.fetch_text_fresh(&entry.url).await?;
+
+    // `Html` is not `Send`, so pull out everything we need before awaiting
+    let (meta, embeds, blurbs, external_media) = {
+        let doc = Html::parse_document(&html);
+        let embeds = find_embeds(&doc);
+
+        // the same player may be embedded more than once, keep the first blurb
+        let slots: Vec<Embed> = IFRAME
+            .select(&doc)
+            .filter_map(|elem| elem.attr("src"))
+            .filter_map(Embed::from_src)
+            .collect();
+        let text = bandcamp::blurbs(doc.root_element(), |elem| {
+            elem.value().name() == "iframe" && elem.attr("src").and_then(Embed::from_src).is_some()
+        });
+        let blurbs: Vec<Option<String>> = embeds
+            .iter()
+            .map(|embed| {
+                let slot = slots.iter().position(|other| other == embed)?;
+                text.get(slot).cloned().flatten()
+            })
+            .collect();
+
+        (
+            BlogMeta::from_generic_html(&doc, entry)?,
+            embeds,
+            blurbs,
+            bandcamp::find_external_media(doc.root_element()),
+        )
+    };
+
+    let mut list = TrackList::default();
+    // the stream URLs are as old as the oldest player
+    let mut fetched: Option<DateTime> = None;
+
+    for (idx, embed) in embeds.iter().enumerate() {
+        let raw = match embed.player_data(client).await {
+            Ok((raw, at)) => {
+                fetched = Some(fetched.map_or(at, |oldest| oldest.min(at)));
+                raw
+            }
+            Err(e) => {
+                tracing::warn!(?embed, "failed fetching embedded player: {e}");
+                continue;
+            }
+        };
+
+        let data: EmbedData = json::from_value(raw.clone()).context(format!(
+            "parsing {} from JSON",
+            std::any::type_name::<EmbedData>()
+        ))?;
+
+        list.raw.push(raw);
+        let blurb = blurbs.get(idx).cloned().flatten();
+        list.tracks.extend(
+            data.into_player_data(embed)
+                .get_tracks(idx + 1, full_album)
+                .into_iter()
+                .map(|track| Track {
+                    blurb: blurb.clone(),
+                    ..track
+                }),
+        );
+    }
+
+    let mut post = BlogPost::new(meta, list);
+    post.external_media = external_media;
+    post.html = Some(html);
+    post.fetched = fetched;
+    Ok(post)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embed_from_src() {
+        assert_eq!(
+            Some(Embed {
+                album_id: Some(123),
+                track_id: Some(456),
+            }),
+            Embed::from_src(
+                "https://bandcamp.com/EmbeddedPlayer/album=123/size=large/bgcol=ffffff/track=456/transparent=true/"
+            )
+        );
+
+        assert_eq!(
+            Some(Embed {
+                album_id: None,
+                track_id: Some(789),
+            }),
+            Embed::from_src("//bandcamp.com/EmbeddedPlayer.html?track=789&size=small")
+        );
+
+        assert_eq!(None, Embed::from_src("https://www.youtube.com/embed/abc"));
+    }
+}
@@ -0,0 +1,498 @@
+//! A Bandcamp fan's public collection or wishlist, read as a [`BlogPost`]
+//! with the featured track of every item.
+
+use crate::bandcamp::{selector, BlogMeta, BlogPost, PlayerData, TrackInfo, TrackList};
+use crate::feed::Entry;
+use crate::types::Duration;
+use anyhow::Context;
+use scraper::Html;
+use serde_json as json;
+use std::collections::{BTreeMap, HashMap};
+
+const BASE_URL: &str = "https://bandcamp.com";
+const API_URL: &str = "https://bandcamp.com/api/fancollection/1";
+const PAGE_SIZE: usize = 100;
+
+/// Stop paging after this many pages, whatever the item count says
+const MAX_PAGES: usize = 500;
+const ARTIST_DOMAIN: &str = ".bandcamp.com";
+
+/// Top-level paths on bandcamp.com that aren't fan pages
+const NON_FAN_PATHS: &[&str] = &[
+    "EmbeddedPlayer",
+    "EmbeddedPlayer.html",
+    "api",
+    "discover",
+    "search",
+    "tag",
+    "tags",
+    "login",
+    "signup",
+    "help",
+    "about",
+    "terms_of_use",
+    "privacy",
+    "artists",
+    "fans",
+    "labels",
+];
+
+selector!(PAGE_DATA, "#pagedata[data-blob]");
+
+/// Which list of a fan's items to read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub(crate) enum List {
+    Collection,
+    Wishlist,
+}
+
+impl List {
+    /// Parses `https://bandcamp.com/USERNAME` and
+    /// `https://bandcamp.com/USERNAME/wishlist`
+    pub(crate) fn from_url(url: &str) -> Option<Self> {
+        let path = url
+            .strip_prefix(BASE_URL)
+            .or_else(|| url.strip_prefix("http://bandcamp.com"))?;
+        let path = path.split(['?', '#']).next()?;
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+        match segments[..] {
+            [name, ..] if NON_FAN_PATHS.contains(&name) => None,
+            [_] | [_, "collection"] => Some(Self::Collection),
+            [_, "wishlist"] => Some(Self::Wishlist),
+            _ => None,
+        }
+    }
+
+    fn api_url(&self) -> String {
+        format!("{API_URL}/{self}_items")
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+struct FanTrack {
+    id: Option<u64>,
+    title: String,
+    artist: Option<String>,
+    #[serde(alias = "track_num")]
+    track_number: Option<usize>,
+    #[serde(default, deserialize_with = "crate::util::duration_from_f64")]
+    duration: Duration,
+    #[serde(default)]
+    file: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+struct Item {
+    tralbum_type: String,
+    tralbum_id: u64,
+    band_id: Option<u64>,
+    band_name: String,
+    item_title: String,
+    item_url: Option<String>,
+    item_art_id: Option<u64>,
+    album_id: Option<u64>,
+    album_title: Option<String>,
+    featured_track: Option<u64>,
+    featured_track_title: Option<String>,
+    featured_track_number: Option<usize>,
+    #[serde(default, deserialize_with = "crate::util::duration_from_f64")]
+    featured_track_duration: Duration,
+    added: Option<String>,
+}
+
+impl Item {
+    /// Key used for the item in `tracklists`
+    fn key(&self) -> String {
+        format!("{}{}", self.tralbum_type, self.tralbum_id)
+    }
+
+    fn into_player_data(self, tracks: Vec<FanTrack>) -> PlayerData {
+        let album_id = match self.tralbum_type.as_str() {
+            "a" => Some(self.tralbum_id),
+            _ => self.album_id,
+        };
+
+        let mut tracklist: Vec<TrackInfo> = tracks
+            .into_iter()
+            .enumerate()
+            .map(|(i, t)| TrackInfo {
+                artist: t.artist.unwrap_or_else(|| self.band_name.clone()),
+                audio_track_duration: t.duration,
+                track_number: t.track_number.unwrap_or(i + 1),
+                track_title: t.title,
+                audio_url: t.file.unwrap_or_default(),
+                album_id,
+                track_id: t.id,
+            })
+            .collect();
+
+        let featured = self
+            .featured_track
+            .and_then(|id| tracklist.iter().find(|t| t.track_id == Some(id)))
+            .map(|t| t.track_number);
+
+        // no streamable tracklist: still worth a Spotify search
+        let featured_track_number = match featured {
+            Some(number) => number,
+            None => {
+                let number = self.featured_track_number.unwrap_or(1);
+                tracklist.retain(|t| t.track_number != number);
+                tracklist.push(TrackInfo {
+                    artist: self.band_name.clone(),
+                    audio_track_duration: self.featured_track_duration,
+                    track_number: number,
+                    track_title: self
+                        .featured_track_title
+                        .clone()
+                        .unwrap_or_else(|| self.item_title.clone()),
+                    audio_url: BTreeMap::new(),
+                    album_id,
+                    track_id: self.featured_track,
+                });
+                number
+            }
+        };
+
+        let band_url = self
+            .item_url
+            .as_deref()
+            .and_then(|url| {
+                url.find(ARTIST_DOMAIN)
+                    .map(|end| &url[..end + ARTIST_DOMAIN.len()])
+            })
+            .map(str::to_owned);
+
+        PlayerData {
+            title: self.album_title.unwrap_or(self.item_title),
+            tracklist,
+            featured_track_number,
+            band_name: self.band_name,
+            band_id: self.band_id,
+            band_location: None,
+            band_url,
+            tralbum_url: self.item_url,
+            art_id: self.item_art_id,
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ListData {
+    #[serde(default)]
+    sequence: Vec<String>,
+    last_token: Option<String>,
+    item_count: usize,
+}
+
+/// Reads a map of `what` keyed by item, skipping entries that don't parse
+/// so that one odd item doesn't lose the whole list.
+fn keyed<T: serde::de::DeserializeOwned>(
+    value: &json::Value,
+    what: &str,
+) -> anyhow::Result<HashMap<String, T>> {
+    let map = value
+        .as_object()
+        .ok_or_else(|| anyhow::format_err!("no {what} in page data"))?;
+
+    Ok(map
+        .iter()
+        .filter_map(|(key, value)| match json::from_value(value.clone()) {
+            Ok(parsed) => Some((key.clone(), parsed)),
+            Err(e) => {
+                tracing::warn!(key, "skipping unreadable {what}: {e}");
+                None
+            }
+        })
+        .collect())
+}
+
+/// The first page of items, from the `#pagedata` blob of the fan page
+#[derive(Debug)]
+struct FanPage {
+    fan_id: u64,
+    fan_name: String,
+    items: Vec<Item>,
+    tracklists: HashMap<String, Vec<FanTrack>>,
+    last_token: Option<String>,
+    item_count: usize,
+}
+
+impl FanPage {
+    fn from_html(html: &str, list: List) -> anyhow::Result<Self> {
+        let doc = Html::parse_document(html);
+
+        let blob: json::Value = PAGE_DATA
+            .select(&doc)
+            .find_map(|elem| elem.attr("data-blob"))
+            .ok_or_else(|| anyhow::format_err!("no fan page data found"))
+            .and_then(|data| json::from_str(data).context("parsing 'data-blob' as JSON"))?;
+
+        let fan_id = blob["fan_data"]["fan_id"]
+            .as_u64()
+            .ok_or_else(|| anyhow::format_err!("no fan id in page data"))?;
+        let fan_name = blob["fan_data"]["name"]
+            .as_str()
+            .or(blob["fan_data"]["username"].as_str())
+            .unwrap_or_default()
+            .to_owned();
+
+        let data: ListData = json::from_value(blob[format!("{list}_data")].clone())
+            .with_context(|| format!("parsing {list} data from JSON"))?;
+
+        let mut cache: HashMap<String, Item> =
+            keyed(&blob["item_cache"][list.to_string()], "item cache")?;
+
+        let items = data
+            .sequence
+            .iter()
+            .filter_map(|key| cache.remove(key))
+            .collect();
+
+        let tracklists = keyed(&blob["tracklists"][list.to_string()], "tracklist")?;
+
+        Ok(Self {
+            fan_id,
+            fan_name,
+            items,
+            tracklists,
+            last_token: data.last_token,
+            item_count: data.item_count,
+        })
+    }
+}
+
+/// A page of items from the fan collection API
+#[derive(Debug, serde::Deserialize)]
+struct ItemsResponse {
+    items: Vec<Item>,
+    #[serde(default)]
+    more_available: bool,
+    last_token: Option<String>,
+    #[serde(default)]
+    tracklists: HashMap<String, Vec<FanTrack>>,
+}
+
+/// The token for the page after `res`, or `None` once paging stops making
+/// progress. Hidden items count towards `item_count` but are never sent, so
+/// the count can't be relied on to end the loop.
+fn next_token(older_than: &str, res: &ItemsResponse) -> Option<String> {
+    if !res.more_available || res.items.is_empty() {
+        return None;
+    }
+
+    res.last_token.clone().filter(|token| token != older_than)
+}
+
+async fn fetch_items(
+    client: &crate::http::Client,
+    list: List,
+    fan_id: u64,
+    token: &str,
+) -> anyhow::Result<ItemsResponse> {
+    let body = json::json!({
+        "fan_id": fan_id,
+        "older_than_token": token,
+        "count": PAGE_SIZE,
+    });
+
+    let req = client
+        .post(list.api_url())
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .build()?;
+
+    let text = client
+        .execute(req)
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    json::from_str(&text).context(format!(
+        "parsing {} from JSON",
+        std::any::type_name::<ItemsResponse>()
+    ))
+}
+
+/// Builds a [`BlogPost`] from every item in a fan's collection or wishlist.
+pub(crate) async fn blog_post(
+    entry: &Entry,
+    client: &crate::http::Client,
+) -> anyhow::Result<BlogPost> {
+    let list = List::from_url(&entry.url)
+        .ok_or_else(|| anyhow::format_err!("not a fan page: {}", entry.url))?;
+    // the rest of the list comes from the API afterwards, so the page is
+    // the oldest part
+    let (html, fetched) = client.fetch_text_fresh(&entry.url).await?;
+
+    let (meta, mut page) = {
+        let doc = Html::parse_document(&html);
+        (
+            BlogMeta::from_generic_html(&doc, entry)?,
+            FanPage::from_html(&html, list)?,
+        )
+    };
+
+    let mut token = page.last_token.take();
+    let mut pages = 0;
+    while let Some(older_than) = token.take() {
+        if page.items.len() >= page.item_count {
+            break;
+        }
+
+        if pages == MAX_PAGES {
+            tracing::warn!(
+                url = entry.url,
+                "giving up on fan items after {pages} pages"
+            );
+            break;
+        }
+        pages += 1;
+
+        let res = fetch_items(client, list, page.fan_id, &older_than).await?;
+        tracing::debug!(
+            url = entry.url,
+            items = res.items.len(),
+            "fetched page of fan items"
+        );
+
+        token = next_token(&older_than, &res);
+        page.items.extend(res.items);
+        page.tracklists.extend(res.tracklists);
+    }
+
+    // the oldest item keeps the post directory stable as the list grows
+    let published = page
+        .items
+        .iter()
+        .filter_map(|item| item.added.as_deref())
+        .filter_map(crate::tralbum::parse_date)
+        .min()
+        .unwrap_or(meta.published);
+
+    let mut list_tracks = TrackList::default();
+    for (idx, item) in page.items.into_iter().enumerate() {
+        let tracks = page.tracklists.remove(&item.key()).unwrap_or_default();
+        list_tracks
+            .tracks
+            .extend(item.into_player_data(tracks).get_track(idx + 1));
+    }
+
+    let title = match list {
+        List::Collection => format!("{} - collection", page.fan_name),
+        List::Wishlist => format!("{} - wishlist", page.fan_name),
+    };
+
+    let mut post = BlogPost::new(
+        BlogMeta {
+            title,
+            published,
+            modified: published,
+            ..meta
+        },
+        list_tracks,
+    );
+    post.html = Some(html);
+    post.fetched = Some(fetched);
+    Ok(post)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fan_page() {
+        assert_eq!(
+            Some(List::Collection),
+            List::from_url("https://bandcamp.com/somefan")
+        );
+        assert_eq!(
+            Some(List::Wishlist),
+            List::from_url("https://bandcamp.com/somefan/wishlist?from=menubar")
+        );
+        assert_eq!(
+            None,
+            List::from_url("https://bandcamp.com/EmbeddedPlayer/album=1")
+        );
+        assert_eq!(
+            None,
+            List::from_url("https://artist.bandcamp.com/album/some-album")
+        );
+
+        let blob = json::json!({
+            "fan_data": {"fan_id": 99, "name": "Some Fan"},
+            "wishlist_data": {"sequence": ["a1", "t2"], "last_token": "abc", "item_count": 3},
+            "item_cache": {"wishlist": {
+                "t2": {"tralbum_type": "t", "tralbum_id": 2, "band_name": "Other", "item_title": "Single",
+                       "item_url": "https://other.bandcamp.com/track/single", "featured_track": 2,
+                       "featured_track_title": "Single", "featured_track_duration": 100.0},
+                "a1": {"tralbum_type": "a", "tralbum_id": 1, "band_id": 5, "band_name": "Band", "item_title": "Album",
+                       "item_url": "https://band.bandcamp.com/album/album", "item_art_id": 77, "featured_track": 11}
+            }},
+            "tracklists": {"wishlist": {
+                "a1": [
+                    {"id": 10, "title": "One", "track_number": 1, "duration": 60.0, "file": {"mp3-128": "https://t4.bcbits.com/stream/10"}},
+                    {"id": 11, "title": "Two", "artist": "Guest", "track_number": 2, "duration": 90.0, "file": {"mp3-128": "https://t4.bcbits.com/stream/11"}}
+                ]
+            }}
+        });
+        let html = format!(
+            r#"<html><body><div id="pagedata" data-blob="{}"></div></body></html>"#,
+            blob.to_string().replace('"', "&quot;")
+        );
+
+        let mut page = FanPage::from_html(&html, List::Wishlist).unwrap();
+        assert_eq!(3, page.item_count);
+        assert_eq!(99, page.fan_id);
+        assert_eq!(Some("abc".to_string()), page.last_token);
+        assert_eq!(2, page.items.len());
+
+        let album = page.items.remove(0);
+        let tracks = page.tracklists.remove(&album.key()).unwrap();
+        let track = album.into_player_data(tracks).get_track(1).unwrap();
+        assert_eq!("Two", track.title);
+        assert_eq!("Guest", track.artist.name);
+        assert_eq!("Band", track.album_artist.name);
+        assert_eq!(
+            Some("https://band.bandcamp.com"),
+            track.album_artist.bandcamp_url.as_deref()
+        );
+        assert_eq!(
+            Some("https://t4.bcbits.com/stream/11"),
+            track.download_url.as_deref()
+        );
+
+        let single = page.items.remove(0);
+        let track = single.into_player_data(vec![]).get_track(2).unwrap();
+        assert_eq!("Single", track.title);
+        assert_eq!("Single", track.album.title);
+        assert_eq!(None, track.download_url);
+
+        // without a count there's no telling when to stop paging
+        let html = html.replace("&quot;item_count&quot;:3", "&quot;other&quot;:3");
+        assert!(FanPage::from_html(&html, List::Wishlist).is_err());
+    }
+
+    #[test]
+    fn stalled_paging() {
+        let res = |items: json::Value, last_token: &str| -> ItemsResponse {
+            json::from_value(json::json!({
+                "items": items,
+                "more_available": true,
+                "last_token": last_token,
+            }))
+            .unwrap()
+        };
+        let item = json::json!([{"tralbum_type": "a", "tralbum_id": 1, "band_name": "Band", "item_title": "Album"}]);
+
+        assert_eq!(
+            Some("def".to_string()),
+            next_token("abc", &res(item.clone(), "def"))
+        );
+        assert_eq!(None, next_token("abc", &res(item, "abc")));
+        assert_eq!(None, next_token("abc", &res(json::json!([]), "def")));
+    }
+}
@@ -1,14 +1,291 @@
-use crate::bandcamp::FEED_URL;
-use rss::Channel;
-
-pub(crate) async fn urls() -> anyhow::Result<Vec<String>> {
-    let content = reqwest::get(FEED_URL).await?.bytes().await?;
-
-    Ok(Channel::read_from(&content[..])?
-        .into_items()
-        .drain(..)
-        .map(|item| item.link)
-        .filter(Option::is_some)
-        .flatten()
-        .collect())
+use crate::source::{Extractor, Source};
+use crate::state;
+use crate::types::DateTime;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use rss::{Channel, Item};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+const CURSOR_FILENAME: &str = "feed.json";
+
+/// A post to be scanned, along with whatever metadata we know about it
+/// before fetching the post itself.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct Entry {
+    pub(crate) url: String,
+    pub(crate) title: Option<String>,
+    pub(crate) categories: Vec<String>,
+    pub(crate) author: Option<String>,
+    pub(crate) published: Option<DateTime>,
+    pub(crate) extractor: Extractor,
+}
+
+impl Entry {
+    /// Categories used for filtering, as for [`crate::bandcamp::BlogPost`]:
+    /// the feed categories plus the URL section.
+    pub(crate) fn filter_categories(&self) -> Vec<String> {
+        let mut categories = self.categories.clone();
+        categories.extend(crate::bandcamp::url_section(&self.url));
+        categories
+    }
+}
+
+impl From<String> for Entry {
+    fn from(url: String) -> Self {
+        Self {
+            url,
+            ..Default::default()
+        }
+    }
+}
+
+/// A feed item, from either an RSS or an Atom feed
+#[derive(Debug, Clone, Default)]
+struct FeedItem {
+    guid: Option<String>,
+    entry: Entry,
+}
+
+fn rss_published(item: &Item) -> Option<DateTime> {
+    item.pub_date()
+        .and_then(|date| chrono::DateTime::parse_from_rfc2822(date).ok())
+        .map(|date| date.to_utc())
+}
+
+impl FeedItem {
+    fn from_rss(item: Item) -> Option<Self> {
+        let author = item
+            .dublin_core_ext()
+            .and_then(|dc| dc.creators().first())
+            .or(item.author.as_ref())
+            .cloned();
+
+        Some(Self {
+            guid: item.guid().map(|guid| guid.value().to_owned()),
+            entry: Entry {
+                title: item.title.clone(),
+                categories: item
+                    .categories()
+                    .iter()
+                    .map(|cat| cat.name().to_owned())
+                    .collect(),
+                author,
+                published: rss_published(&item),
+                url: item.link?,
+                ..Default::default()
+            },
+        })
+    }
+
+    fn from_atom(entry: &atom_syndication::Entry) -> Option<Self> {
+        let url = entry
+            .links()
+            .iter()
+            .find(|link| link.rel() == "alternate")
+            .or(entry.links().first())?
+            .href()
+            .to_owned();
+
+        Some(Self {
+            guid: Some(entry.id().to_owned()),
+            entry: Entry {
+                url,
+                title: Some(entry.title().value.clone()),
+                categories: entry
+                    .categories()
+                    .iter()
+                    .map(|cat| cat.label().unwrap_or(cat.term()).to_owned())
+                    .collect(),
+                author: entry.authors().first().map(|a| a.name().to_owned()),
+                published: Some(entry.published().unwrap_or(entry.updated()).to_utc()),
+                ..Default::default()
+            },
+        })
+    }
+}
+
+fn parse_items(content: &[u8]) -> anyhow::Result<Vec<FeedItem>> {
+    match Channel::read_from(content) {
+        Ok(channel) => Ok(channel
+            .into_items()
+            .into_iter()
+            .filter_map(FeedItem::from_rss)
+            .collect()),
+        Err(rss_error) => match atom_syndication::Feed::read_from(content) {
+            Ok(feed) => Ok(feed
+                .entries()
+                .iter()
+                .filter_map(FeedItem::from_atom)
+                .collect()),
+            Err(atom_error) => {
+                anyhow::bail!("not a valid RSS ({rss_error}) or Atom ({atom_error}) feed")
+            }
+        },
+    }
+}
+
+/// Where we left off with a feed: HTTP validators for conditional requests,
+/// and the newest item we've seen.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Cursor {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    last_guid: Option<String>,
+    last_published: Option<DateTime>,
+}
+
+impl Cursor {
+    fn is_new(&self, item: &FeedItem) -> bool {
+        if let (Some(last), Some(guid)) = (&self.last_guid, &item.guid) {
+            if guid == last {
+                return false;
+            }
+        }
+
+        match (self.last_published, item.entry.published) {
+            (Some(last), Some(published)) => published > last,
+            _ => true,
+        }
+    }
+}
+
+/// Saved cursors for every feed we follow, keyed by feed URL.
+#[derive(Debug)]
+pub(crate) struct Feeds {
+    path: PathBuf,
+    cursors: BTreeMap<String, Cursor>,
+    pending: BTreeMap<String, Cursor>,
+}
+
+impl Feeds {
+    pub(crate) fn load(dir: &str) -> Self {
+        let path = PathBuf::from(dir).join(CURSOR_FILENAME);
+
+        let cursors = state::load(&path).unwrap_or_else(|e| {
+            tracing::debug!("no feed cursor loaded from {path:?}: {e}");
+            BTreeMap::new()
+        });
+
+        Self {
+            path,
+            cursors,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Fetches feed items that are newer than the saved cursor.
+    ///
+    /// With `force`, the cursor is ignored and every item in the feed is
+    /// returned.
+    pub(crate) async fn entries(
+        &mut self,
+        source: &Source,
+        client: &crate::http::Client,
+        force: bool,
+    ) -> anyhow::Result<Vec<Entry>> {
+        let url = &source.feed_url;
+
+        let cursor = match self.cursors.get(url) {
+            Some(cursor) if !force => cursor.clone(),
+            _ => Cursor::default(),
+        };
+
+        let mut req = client.get(url);
+        if let Some(etag) = &cursor.etag {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &cursor.last_modified {
+            req = req.header(IF_MODIFIED_SINCE, last_modified);
+        }
+
+        let res = client.execute(req.build()?).await?;
+
+        if res.status() == StatusCode::NOT_MODIFIED {
+            tracing::info!(url, "feed has not changed");
+            return Ok(vec![]);
+        }
+
+        let res = res.error_for_status()?;
+
+        let header = |name| {
+            res.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_owned)
+        };
+
+        let mut next = Cursor {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            ..cursor.clone()
+        };
+
+        let content = res.bytes().await?;
+        let items: Vec<FeedItem> = parse_items(&content[..])?
+            .into_iter()
+            .take_while(|item| cursor.is_new(item))
+            .collect();
+
+        if let Some(newest) = items.iter().max_by_key(|item| item.entry.published) {
+            next.last_guid = newest.guid.clone();
+            next.last_published = newest.entry.published;
+        }
+
+        self.pending.insert(url.clone(), next);
+
+        let entries: Vec<Entry> = items
+            .into_iter()
+            .map(|item| Entry {
+                extractor: source.extractor,
+                ..item.entry
+            })
+            .collect();
+
+        tracing::info!(url, new = entries.len(), "fetched feed");
+
+        Ok(entries)
+    }
+
+    /// Saves the cursors from the last fetch. Call this only once the
+    /// returned entries have been processed.
+    pub(crate) fn commit(&mut self) -> anyhow::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        self.cursors.append(&mut self.pending);
+        state::save(&self.cursors, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(guid: &str, pub_date: &str) -> FeedItem {
+        let mut item = Item::default();
+        item.set_guid(rss::Guid {
+            value: guid.to_string(),
+            permalink: false,
+        });
+        item.set_pub_date(pub_date.to_string());
+        item.set_link("https://daily.bandcamp.com/features/post".to_string());
+        FeedItem::from_rss(item).unwrap()
+    }
+
+    #[test]
+    fn cursor_is_new() {
+        let cursor = Cursor {
+            last_guid: Some("b".to_string()),
+            last_published: item("b", "Tue, 10 Dec 2024 12:00:00 +0000").entry.published,
+            ..Default::default()
+        };
+
+        assert!(cursor.is_new(&item("c", "Wed, 11 Dec 2024 12:00:00 +0000")));
+        assert!(!cursor.is_new(&item("b", "Tue, 10 Dec 2024 12:00:00 +0000")));
+        assert!(!cursor.is_new(&item("a", "Mon, 09 Dec 2024 12:00:00 +0000")));
+
+        assert!(Cursor::default().is_new(&item("a", "Mon, 09 Dec 2024 12:00:00 +0000")));
+    }
 }
@@ -1,23 +1,673 @@
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CACHE_CONTROL, PRAGMA, REFERER};
-use reqwest::Client;
+use crate::cache::{Cached, Meta};
+use crate::metrics;
+use crate::types::{DateTime, Duration};
+use futures::stream::{Stream, StreamExt};
+use rand::Rng;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    LAST_MODIFIED, PRAGMA, REFERER, RETRY_AFTER,
+};
+use reqwest::{IntoUrl, Method, Request, RequestBuilder, StatusCode};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
 
-const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:133.0) Gecko/20100101 Firefox/133.0";
+pub(crate) const DEFAULT_USER_AGENT: &str =
+    "Mozilla/5.0 (X11; Linux x86_64; rv:133.0) Gecko/20100101 Firefox/133.0";
 const DEFAULT_HEADERS: &[(HeaderName, &str)] = &[
     (PRAGMA, "no-cache"),
     (CACHE_CONTROL, "no-cache"),
     (REFERER, "https://daily.bandcamp.com/"),
 ];
 
-pub(crate) fn client() -> reqwest::Client {
-    let headers = HeaderMap::from_iter(
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Applies between reads of the response body, so large downloads aren't
+/// cut off as long as data keeps coming
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+const MAX_RETRIES: u32 = 4;
+const BASE_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Servers asking us to wait longer than this get a failure instead
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+const RETRY_STATUSES: &[StatusCode] = &[
+    StatusCode::REQUEST_TIMEOUT,
+    StatusCode::TOO_MANY_REQUESTS,
+    StatusCode::INTERNAL_SERVER_ERROR,
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+pub(crate) const DEFAULT_MAX_PER_HOST: usize = 4;
+pub(crate) const DEFAULT_REQUESTS_PER_SECOND: f64 = 4.0;
+
+/// Anything slower than one request every ~17 minutes is surely a typo
+const MIN_REQUESTS_PER_SECOND: f64 = 0.001;
+
+/// How hard we're willing to hit any one host
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Limits {
+    /// Requests in flight at once, including reading the response body
+    pub(crate) max_per_host: usize,
+
+    /// Requests started per second; zero for no limit
+    pub(crate) requests_per_second: f64,
+}
+
+/// How we present ourselves on every request
+#[derive(Debug, Clone)]
+pub(crate) struct Identity {
+    pub(crate) user_agent: String,
+    pub(crate) proxy: Option<url::Url>,
+
+    /// Sent in addition to, or in place of, the default headers
+    pub(crate) headers: Vec<(HeaderName, HeaderValue)>,
+
+    /// A Netscape-format cookies.txt, as exported by browsers and curl
+    pub(crate) cookies: Option<PathBuf>,
+}
+
+/// Parses a `Name: value` header.
+pub(crate) fn parse_header(s: &str) -> anyhow::Result<(HeaderName, HeaderValue)> {
+    let Some((name, value)) = s.split_once(':') else {
+        anyhow::bail!("expected 'Name: value', got '{s}'");
+    };
+
+    Ok((name.trim().parse()?, value.trim().parse()?))
+}
+
+/// Parses a `--requests-per-second` rate: zero, or a rate we can turn into
+/// a delay between requests.
+pub(crate) fn parse_requests_per_second(s: &str) -> anyhow::Result<f64> {
+    let rate: f64 = s.parse()?;
+
+    if rate != 0.0 && !(MIN_REQUESTS_PER_SECOND..=f64::MAX).contains(&rate) {
+        anyhow::bail!("expected 0 or a rate of at least {MIN_REQUESTS_PER_SECOND}, got '{s}'");
+    }
+
+    Ok(rate)
+}
+
+/// Turns a cookies.txt line into a `Set-Cookie` string and the URL it was
+/// set from, skipping comments and expired cookies.
+fn parse_cookie(line: &str, now: crate::types::DateTime) -> Option<(String, url::Url)> {
+    let line = line.trim();
+    let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let fields: Vec<&str> = line.split('\t').collect();
+    let [domain, include_subdomains, path, secure, expires, name, value] = fields[..] else {
+        tracing::warn!("ignoring malformed cookie line: {line}");
+        return None;
+    };
+
+    let expires: i64 = expires.parse().ok()?;
+    if expires != 0 && expires < now.timestamp() {
+        return None;
+    }
+
+    let host = domain.trim_start_matches('.');
+    let mut cookie = format!("{name}={value}; Path={path}");
+    if include_subdomains == "TRUE" {
+        cookie.push_str(&format!("; Domain={host}"));
+    }
+    if secure == "TRUE" {
+        cookie.push_str("; Secure");
+    }
+
+    let url = format!("https://{host}{path}").parse().ok()?;
+    Some((cookie, url))
+}
+
+fn load_cookies(path: &PathBuf) -> anyhow::Result<reqwest::cookie::Jar> {
+    let jar = reqwest::cookie::Jar::default();
+    let now = chrono::Utc::now();
+
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::format_err!("reading cookies from {}: {e}", path.display()))?;
+
+    let mut count = 0;
+    for (cookie, url) in text.lines().filter_map(|line| parse_cookie(line, now)) {
+        jar.add_cookie_str(&cookie, &url);
+        count += 1;
+    }
+
+    tracing::debug!("loaded {count} cookies from {}", path.display());
+    Ok(jar)
+}
+
+#[derive(Debug)]
+struct Host {
+    permits: Arc<Semaphore>,
+    next_start: Mutex<Instant>,
+}
+
+#[derive(Debug)]
+struct Limiter {
+    limits: Limits,
+    hosts: Mutex<HashMap<String, Arc<Host>>>,
+}
+
+impl Limiter {
+    fn host(&self, name: &str) -> Arc<Host> {
+        self.hosts
+            .lock()
+            .expect("http limiter lock is poisoned!")
+            .entry(name.to_owned())
+            .or_insert_with(|| {
+                Arc::new(Host {
+                    permits: Arc::new(Semaphore::new(self.limits.max_per_host.max(1))),
+                    next_start: Mutex::new(Instant::now()),
+                })
+            })
+            .clone()
+    }
+
+    async fn acquire(&self, host: &Host) -> OwnedSemaphorePermit {
+        host.permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("http limiter semaphore is closed!")
+    }
+
+    /// Waits for the host's next request slot.
+    async fn pace(&self, host: &Host) {
+        if self.limits.requests_per_second <= 0.0 {
+            return;
+        }
+
+        let interval = Duration::from_secs_f64(1.0 / self.limits.requests_per_second);
+        let start = {
+            let mut next = host
+                .next_start
+                .lock()
+                .expect("http limiter lock is poisoned!");
+            let start = (*next).max(Instant::now());
+            *next = start + interval;
+            start
+        };
+
+        tokio::time::sleep_until(start).await;
+    }
+}
+
+/// A response that holds on to its host's concurrency permit until the body
+/// has been read.
+#[derive(Debug)]
+pub(crate) struct Response {
+    inner: reqwest::Response,
+    permit: OwnedSemaphorePermit,
+}
+
+impl Response {
+    pub(crate) fn status(&self) -> StatusCode {
+        self.inner.status()
+    }
+
+    pub(crate) fn headers(&self) -> &HeaderMap {
+        self.inner.headers()
+    }
+
+    pub(crate) fn content_length(&self) -> Option<u64> {
+        self.inner.content_length()
+    }
+
+    pub(crate) fn error_for_status(self) -> reqwest::Result<Self> {
+        let Self { inner, permit } = self;
+        Ok(Self {
+            inner: inner.error_for_status()?,
+            permit,
+        })
+    }
+
+    pub(crate) async fn text(self) -> reqwest::Result<String> {
+        self.inner.text().await
+    }
+
+    pub(crate) async fn bytes(self) -> reqwest::Result<bytes::Bytes> {
+        self.inner.bytes().await
+    }
+
+    pub(crate) fn bytes_stream(self) -> impl Stream<Item = reqwest::Result<bytes::Bytes>> {
+        let permit = self.permit;
+        self.inner.bytes_stream().map(move |chunk| {
+            let _held = &permit;
+            chunk
+        })
+    }
+}
+
+/// The HTTP client used for everything except Spotify. Idempotent requests
+/// are retried on connection errors and transient statuses, and requests to
+/// each host are limited by [`Limits`].
+#[derive(Debug, Clone)]
+pub(crate) struct Client {
+    inner: reqwest::Client,
+    limiter: Arc<Limiter>,
+    cache: Option<Arc<crate::cache::Cache>>,
+}
+
+impl Client {
+    pub(crate) fn with_cache(self, cache: crate::cache::Cache) -> Self {
+        Self {
+            cache: Some(Arc::new(cache)),
+            ..self
+        }
+    }
+
+    /// Fetches a page, going through the on-disk cache if there is one.
+    pub(crate) async fn fetch_text(&self, url: &str) -> anyhow::Result<String> {
+        Ok(self.fetch_text_dated(url).await?.0)
+    }
+
+    /// Like [`Client::fetch_text`], along with when the server last sent the
+    /// page, which is a while ago if it came from the cache.
+    pub(crate) async fn fetch_text_dated(&self, url: &str) -> anyhow::Result<(String, DateTime)> {
+        self.fetch_text_with(url, true).await
+    }
+
+    /// Fetches a page from the server even if it's cached, e.g. because it
+    /// has links that expire, along with when it was fetched. The cache is
+    /// still updated.
+    pub(crate) async fn fetch_text_fresh(&self, url: &str) -> anyhow::Result<(String, DateTime)> {
+        self.fetch_text_with(url, false).await
+    }
+
+    async fn fetch_text_with(
+        &self,
+        url: &str,
+        use_cached: bool,
+    ) -> anyhow::Result<(String, DateTime)> {
+        let cache = self.cache.as_deref();
+        let cached = cache
+            .filter(|_| use_cached)
+            .and_then(|cache| cache.get(url));
+
+        if let (Some(cache), Some(cached)) = (cache, &cached) {
+            if cached.is_fresh(cache.ttl) {
+                tracing::debug!(url, "using cached response");
+                metrics::inc(metrics::HttpCacheHits, 1);
+                return Ok((cached.body.clone(), cached.meta.fetched));
+            }
+        }
+
+        let req = conditional(self.get(url), cached.as_ref()).build()?;
+        let res = self.execute(req).await?;
+
+        if let Some(cached) = revalidated(res.status(), cached) {
+            tracing::debug!(url, "cached response is still valid");
+            metrics::inc(metrics::HttpCacheRevalidations, 1);
+            if let Some(Err(e)) = cache.map(|cache| cache.touch(&cached.meta)) {
+                tracing::warn!(url, "failed updating cache entry: {e}");
+            }
+            // the server vouches for the body, so it's as good as new
+            return Ok((cached.body, chrono::Utc::now()));
+        }
+
+        let res = res.error_for_status()?;
+        let meta = cache_meta(url, res.headers(), chrono::Utc::now());
+
+        let body = res.text().await?;
+        if let Some(Err(e)) = cache.map(|cache| cache.put(&meta, &body)) {
+            tracing::warn!(url, "failed caching response: {e}");
+        }
+
+        Ok((body, meta.fetched))
+    }
+
+    pub(crate) fn get<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.inner.get(url)
+    }
+
+    pub(crate) fn post<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.inner.post(url)
+    }
+
+    pub(crate) async fn execute(&self, req: Request) -> reqwest::Result<Response> {
+        let host = self.limiter.host(req.url().host_str().unwrap_or_default());
+
+        let (inner, permit) = self.execute_with_retries(req, &host).await?;
+        Ok(Response { inner, permit })
+    }
+
+    async fn execute_with_retries(
+        &self,
+        mut req: Request,
+        host: &Host,
+    ) -> reqwest::Result<(reqwest::Response, OwnedSemaphorePermit)> {
+        let mut attempt = 0;
+
+        loop {
+            let permit = self.limiter.acquire(host).await;
+            self.limiter.pace(host).await;
+
+            let next = if is_idempotent(req.method()) && attempt < MAX_RETRIES {
+                req.try_clone()
+            } else {
+                None
+            };
+
+            let url = req.url().to_string();
+            let res = self.inner.execute(req).await;
+
+            let Some(next) = next else {
+                return res.map(|res| (res, permit));
+            };
+
+            let delay = match &res {
+                Ok(res) if RETRY_STATUSES.contains(&res.status()) => {
+                    retry_after(res).unwrap_or_else(|| backoff(attempt))
+                }
+                Err(e) if is_transient(e) => backoff(attempt),
+                _ => return res.map(|res| (res, permit)),
+            };
+
+            if delay > MAX_RETRY_AFTER {
+                tracing::warn!(url, "server asked us to wait {delay:?}, giving up");
+                return res.map(|res| (res, permit));
+            }
+
+            attempt += 1;
+            metrics::inc(metrics::HttpRetries, 1);
+
+            match &res {
+                Ok(res) => {
+                    tracing::warn!(url, attempt, status = %res.status(), "retrying in {delay:?}")
+                }
+                Err(e) => tracing::warn!(url, attempt, "retrying in {delay:?}: {e}"),
+            }
+
+            // let other requests to the host go ahead while we wait
+            drop(res);
+            drop(permit);
+
+            tokio::time::sleep(delay).await;
+            req = next;
+        }
+    }
+}
+
+/// Asks the server to only send the page if it has changed since we cached it.
+fn conditional(mut req: RequestBuilder, cached: Option<&Cached>) -> RequestBuilder {
+    if let Some(meta) = cached.map(|c| &c.meta) {
+        if let Some(etag) = &meta.etag {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &meta.last_modified {
+            req = req.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    req
+}
+
+/// The cached copy, if the server says it's still current.
+fn revalidated(status: StatusCode, cached: Option<Cached>) -> Option<Cached> {
+    cached.filter(|_| status == StatusCode::NOT_MODIFIED)
+}
+
+/// What to cache a new response under, so that it can be revalidated later.
+fn cache_meta(url: &str, headers: &HeaderMap, now: DateTime) -> Meta {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_owned)
+    };
+
+    Meta {
+        url: url.to_owned(),
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+        fetched: now,
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE
+    )
+}
+
+/// Failures worth another try: the connection couldn't be made, timed out,
+/// or dropped part way through.
+fn is_transient(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout() || e.is_body() || is_connection_lost(e)
+}
+
+fn is_connection_lost(e: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(e);
+
+    while let Some(e) = source {
+        if let Some(io) = e.downcast_ref::<std::io::Error>() {
+            if matches!(
+                io.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof
+            ) {
+                return true;
+            }
+        }
+        source = e.source();
+    }
+
+    false
+}
+
+/// Exponential backoff with jitter, so that concurrent requests that failed
+/// together don't retry together.
+fn backoff(attempt: u32) -> Duration {
+    let delay = BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_DELAY);
+
+    delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+}
+
+/// Parses `Retry-After` as either a number of seconds or an HTTP date.
+fn retry_after(res: &reqwest::Response) -> Option<Duration> {
+    let value = res.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    parse_retry_after(value, chrono::Utc::now())
+}
+
+fn parse_retry_after(value: &str, now: crate::types::DateTime) -> Option<Duration> {
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.to_utc() - now).to_std().unwrap_or_default())
+}
+
+/// Our default headers, with any given on the command line replacing them.
+/// A header given more than once sends every value.
+fn default_headers(identity: &Identity) -> HeaderMap {
+    let mut headers = HeaderMap::from_iter(
         DEFAULT_HEADERS
             .iter()
             .map(|(name, value)| (name.clone(), HeaderValue::from_static(value))),
     );
 
-    Client::builder()
-        .user_agent(USER_AGENT)
-        .default_headers(headers)
-        .build()
-        .expect("unreachable!")
+    for (name, _) in &identity.headers {
+        headers.remove(name);
+    }
+    for (name, value) in &identity.headers {
+        headers.append(name.clone(), value.clone());
+    }
+
+    headers
+}
+
+pub(crate) fn client(limits: Limits, identity: &Identity) -> anyhow::Result<Client> {
+    let mut builder = reqwest::Client::builder()
+        .user_agent(&identity.user_agent)
+        .default_headers(default_headers(identity))
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT);
+
+    if let Some(proxy) = &identity.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy.clone())?);
+    }
+
+    if let Some(path) = &identity.cookies {
+        builder = builder.cookie_provider(Arc::new(load_cookies(path)?));
+    }
+
+    Ok(Client {
+        inner: builder.build()?,
+        limiter: Arc::new(Limiter {
+            limits,
+            hosts: Mutex::new(HashMap::new()),
+        }),
+        cache: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delays() {
+        let now = "2024-01-05T00:00:00Z".parse().unwrap();
+        assert_eq!(
+            Some(Duration::from_secs(120)),
+            parse_retry_after("120", now)
+        );
+        assert_eq!(
+            Some(Duration::from_secs(90)),
+            parse_retry_after("Fri, 05 Jan 2024 00:01:30 GMT", now)
+        );
+        assert_eq!(
+            Some(Duration::ZERO),
+            parse_retry_after("Thu, 04 Jan 2024 00:00:00 GMT", now)
+        );
+        assert_eq!(None, parse_retry_after("soon", now));
+
+        for attempt in 0..10 {
+            let delay = backoff(attempt);
+            assert!(delay >= BASE_DELAY / 2 && delay <= MAX_DELAY, "{delay:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn transient_errors() {
+        let client = reqwest::Client::new();
+
+        let bad_url = client.get("not a url").build().unwrap_err();
+        assert!(!is_transient(&bad_url));
+
+        // nothing listens on port 1
+        let refused = client.get("http://127.0.0.1:1/").send().await.unwrap_err();
+        assert!(is_transient(&refused));
+
+        let reset = anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::ConnectionReset))
+            .context("sending request");
+        assert!(is_connection_lost(reset.as_ref()));
+
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert!(!is_connection_lost(&denied));
+    }
+
+    #[test]
+    fn conditional_fetch() {
+        let url = "https://daily.bandcamp.com/";
+        let header = |req: &Request, name| {
+            req.headers()
+                .get(name)
+                .map(|v: &HeaderValue| v.to_str().unwrap().to_owned())
+        };
+
+        // nothing cached, or no cache at all
+        let req = conditional(reqwest::Client::new().get(url), None)
+            .build()
+            .unwrap();
+        assert_eq!(None, header(&req, IF_NONE_MATCH));
+        assert_eq!(None, header(&req, IF_MODIFIED_SINCE));
+        assert!(revalidated(StatusCode::NOT_MODIFIED, None).is_none());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+        headers.insert(
+            LAST_MODIFIED,
+            HeaderValue::from_static("Fri, 05 Jan 2024 00:00:00 GMT"),
+        );
+        let now = "2024-01-05T00:00:00Z".parse().unwrap();
+        let cached = Cached {
+            meta: cache_meta(url, &headers, now),
+            body: "cached body".to_owned(),
+        };
+
+        let req = conditional(reqwest::Client::new().get(url), Some(&cached))
+            .build()
+            .unwrap();
+        assert_eq!(Some("\"v1\"".to_owned()), header(&req, IF_NONE_MATCH));
+        assert_eq!(
+            Some("Fri, 05 Jan 2024 00:00:00 GMT".to_owned()),
+            header(&req, IF_MODIFIED_SINCE)
+        );
+
+        assert!(revalidated(StatusCode::OK, Some(cached.clone())).is_none());
+        assert_eq!(
+            "cached body",
+            revalidated(StatusCode::NOT_MODIFIED, Some(cached))
+                .unwrap()
+                .body
+        );
+    }
+
+    #[test]
+    fn identity() {
+        let (name, value) = parse_header("X-Forwarded-For:  10.0.0.1 ").unwrap();
+        assert_eq!("x-forwarded-for", name.as_str());
+        assert_eq!("10.0.0.1", value);
+        assert!(parse_header("no colon").is_err());
+
+        assert_eq!(0.0, parse_requests_per_second("0").unwrap());
+        assert_eq!(0.5, parse_requests_per_second("0.5").unwrap());
+        for rate in ["NaN", "inf", "-1", "1e-300"] {
+            assert!(parse_requests_per_second(rate).is_err(), "{rate}");
+        }
+
+        let identity = Identity {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            proxy: None,
+            headers: ["X-Tag: a", "X-Tag: b", "Referer: https://example.com/"]
+                .into_iter()
+                .map(|h| parse_header(h).unwrap())
+                .collect(),
+            cookies: None,
+        };
+        let headers = default_headers(&identity);
+        assert_eq!(2, headers.get_all("x-tag").iter().count());
+        assert_eq!(1, headers.get_all(REFERER).iter().count());
+        assert_eq!("https://example.com/", headers[REFERER]);
+
+        let now = "2024-01-05T00:00:00Z".parse().unwrap();
+        let (cookie, url) = parse_cookie(
+            "#HttpOnly_.bandcamp.com\tTRUE\t/\tTRUE\t0\tidentity\tabc123",
+            now,
+        )
+        .unwrap();
+        assert_eq!(
+            "identity=abc123; Path=/; Domain=bandcamp.com; Secure",
+            cookie
+        );
+        assert_eq!("https://bandcamp.com/", url.as_str());
+
+        assert!(parse_cookie("# Netscape HTTP Cookie File", now).is_none());
+        assert!(parse_cookie("bandcamp.com\tFALSE\t/\tFALSE\t1000\tstale\tx", now).is_none());
+    }
 }
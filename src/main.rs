@@ -1,82 +1,395 @@
+mod audio;
+mod backfill;
 mod bandcamp;
+mod cache;
 mod cli;
+mod cover;
 mod download;
+mod embed;
+mod fan;
 mod feed;
 mod http;
 mod metrics;
+mod progress;
+mod release;
 mod search;
+mod shutdown;
+mod source;
 mod spotify;
 mod state;
 mod tag;
+mod tralbum;
 mod types;
 mod util;
+mod watch;
 
 use anyhow::Context;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use std::collections::HashSet;
 
 #[macro_use]
 extern crate lazy_static;
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let args = cli::args();
+/// How many posts are worked on at once, so that one post's downloads don't
+/// hold up fetching, enriching and downloading the next
+const POSTS_IN_FLIGHT: usize = 4;
+
+async fn spotify_client(args: &cli::Args) -> anyhow::Result<Option<spotify::Client>> {
+    if args.no_spotify || args.offline {
+        Ok(None)
+    } else {
+        Ok(Some(spotify::connect(&args.playlist_name).await?))
+    }
+}
+
+fn wants_full_album(entry: &feed::Entry, args: &cli::Args) -> bool {
+    args.wants_full_album(&entry.filter_categories())
+}
+
+async fn fetch_post(
+    entry: &feed::Entry,
+    args: &cli::Args,
+    client: &http::Client,
+    single_url: bool,
+) -> anyhow::Result<Option<bandcamp::BlogPost>> {
+    let url = &entry.url;
+    tracing::info!("scanning post: {url}");
+    metrics::inc(metrics::BlogPostsSeen, 1);
+
+    let full_album = wants_full_album(entry, args);
+
+    // Bandcamp Daily articles always carry their own date
+    let mut entry = entry.clone();
+    if entry.published.is_none() && entry.extractor != source::Extractor::BandcampDaily {
+        entry.published = Some(state::first_seen(&args.download_to, url)?);
+    }
+
+    let post = entry
+        .extractor
+        .fetch_post(&entry, client, full_album)
+        .await
+        .with_context(|| format!("fetching blog post from {url}"));
+
+    match post {
+        Ok(mut post) => {
+            post.apply_entry(&entry);
+            Ok(Some(post))
+        }
+        Err(e) if single_url => anyhow::bail!(e),
+        Err(e) => {
+            tracing::error!(?e, url);
+            Ok(None)
+        }
+    }
+}
+
+async fn process_post(
+    mut post: bandcamp::BlogPost,
+    args: &cli::Args,
+    client: &http::Client,
+    spotify: Option<&spotify::Client>,
+) -> anyhow::Result<state::State> {
+    let html = post.html.take();
+
+    let mut state = state::State::try_get_or_create(post, &args.download_to)?;
+    metrics::inc(metrics::TracksSeen, state.tracks.len());
+
+    if let Some(html) = html {
+        if let Err(e) = state.archive_html(&html) {
+            tracing::warn!(state.blog_info.url, "failed archiving post HTML: {e}");
+        }
+    }
+
+    if !args.no_enrich && !args.offline {
+        tralbum::enrich(&mut state, client).await;
+        state.save()?;
+    }
+
+    if let Some(spotify) = spotify {
+        spotify.exec(&mut state).await?;
+    }
+
+    if !args.no_download {
+        if !args.offline {
+            download::download(&mut state).await;
+            cover::download(&state, client, args.cover_size).await;
+        }
+        state.save()?;
+
+        tag::tag(&state).await?;
+    }
+
+    Ok(state)
+}
+
+async fn backfill(
+    args: &cli::Args,
+    until: chrono::NaiveDate,
+    client: &http::Client,
+) -> anyhow::Result<()> {
+    // no point walking back past --since
+    let until = args.since.map_or(until, |since| since.max(until));
+    let mut backfill = backfill::Backfill::load(&args.download_to, until)?;
+
+    let spotify = spotify_client(args).await?;
+
+    let complete = skip_urls(args)?;
+
+    while let Some(urls) = backfill.next_page(client).await? {
+        let mut posts = vec![];
+
+        for url in urls {
+            if complete.contains(&url) {
+                tracing::debug!(url, "SKIP: already complete");
+                continue;
+            }
+
+            let entry = feed::Entry::from(url);
+            let Some(post) = fetch_post(&entry, args, client, false).await? else {
+                continue;
+            };
+
+            backfill.saw(&post);
+
+            if backfill.is_before_cutoff(&post) {
+                tracing::debug!(entry.url, "SKIP: published before backfill target date");
+                continue;
+            }
+
+            if !args.wants_categories(&post.filter_categories()) {
+                tracing::debug!(entry.url, "SKIP: excluded by category filter");
+                continue;
+            }
+
+            if !args.in_date_range(&post.published) {
+                tracing::debug!(entry.url, "SKIP: outside of --since/--until");
+                continue;
+            }
+
+            posts.push(post);
+        }
+
+        stream::iter(posts)
+            .map(|post| process_post(post, args, client, spotify.as_ref()))
+            .buffered(POSTS_IN_FLIGHT)
+            .try_for_each(|_| async { Ok(()) })
+            .await?;
+
+        backfill.advance()?;
+    }
+
+    Ok(())
+}
 
-    tracing_subscriber::fmt::init();
+/// Posts that don't need to be scanned again unless `--force` is given.
+fn skip_urls(args: &cli::Args) -> anyhow::Result<HashSet<String>> {
+    if args.force {
+        Ok(HashSet::new())
+    } else {
+        state::complete_urls(args)
+    }
+}
+
+/// New posts from every configured feed that pass the category filters.
+async fn feed_entries(
+    args: &cli::Args,
+    client: &http::Client,
+    feeds: &mut feed::Feeds,
+) -> anyhow::Result<Vec<feed::Entry>> {
+    let mut entries = vec![];
+    // the same post can turn up in more than one feed, and two copies
+    // shouldn't be worked on at once
+    let mut seen = HashSet::new();
+
+    for source in source::sources(args)? {
+        entries.extend(
+            feeds
+                .entries(&source, client, args.force)
+                .await
+                .with_context(|| format!("fetching feed {}", source.feed_url))?
+                .into_iter()
+                .filter(|entry| args.wants_categories(&entry.filter_categories()))
+                .filter(|entry| {
+                    entry
+                        .published
+                        .is_none_or(|published| args.in_date_range(&published))
+                })
+                .filter(|entry| seen.insert(entry.url.clone())),
+        );
+    }
+
+    Ok(entries)
+}
+
+fn skip_complete(args: &cli::Args, entries: Vec<feed::Entry>) -> anyhow::Result<Vec<feed::Entry>> {
+    if entries.is_empty() {
+        return Ok(entries);
+    }
+
+    let complete = skip_urls(args)?;
 
+    Ok(entries
+        .into_iter()
+        .filter(|entry| {
+            let skip = complete.contains(&entry.url);
+            if skip {
+                tracing::debug!(entry.url, "SKIP: already complete");
+            }
+            !skip
+        })
+        .collect())
+}
+
+async fn scan(args: &cli::Args, client: &http::Client) -> anyhow::Result<()> {
     let single_url = args.url.is_some();
 
-    let urls = if args.rescan {
-        state::blog_urls(&args)?
+    let mut feed = None;
+
+    let entries = if args.rescan {
+        state::blog_entries(args)?
     } else {
-        match args.url {
-            None => feed::urls().await?,
-            Some(url) => Vec::from([url]),
-        }
+        let entries = match &args.url {
+            None => {
+                let feeds = feed.insert(feed::Feeds::load(&args.download_to));
+                feed_entries(args, client, feeds).await?
+            }
+            Some(url) => Vec::from([feed::Entry {
+                extractor: source::Extractor::for_post_url(url),
+                ..feed::Entry::from(url.clone())
+            }]),
+        };
+
+        skip_complete(args, entries)?
     };
 
-    if urls.is_empty() {
+    if entries.is_empty() {
+        if let Some(feed) = &mut feed {
+            feed.commit()?;
+        }
+
         tracing::info!("no posts to scrape, exiting");
         return Ok(());
     }
 
-    let spotify = if args.no_spotify {
-        None
-    } else {
-        Some(spotify::connect().await?)
-    };
+    let spotify = spotify_client(args).await?;
+    let spotify = spotify.as_ref();
 
-    let client = http::client();
-    for url in urls {
-        tracing::info!("scanning post: {url}");
-        metrics::inc(metrics::BlogPostsSeen, 1);
+    // whether each post could be fetched
+    let mut fetched = stream::iter(entries)
+        .map(|entry| async move {
+            let Some(post) = fetch_post(&entry, args, client, single_url).await? else {
+                return Ok(false);
+            };
 
-        let post = bandcamp::BlogPost::try_from_url(&url, &client)
-            .await
-            .with_context(|| format!("fetching blog post from {url}"));
+            if !single_url && !args.in_date_range(&post.published) {
+                tracing::debug!(entry.url, "SKIP: outside of --since/--until");
+                return Ok(true);
+            }
+
+            process_post(post, args, client, spotify).await?;
+            anyhow::Ok(true)
+        })
+        .buffered(POSTS_IN_FLIGHT);
+
+    let mut failed = false;
+    while let Some(ok) = fetched.try_next().await? {
+        failed |= !ok;
+    }
+
+    if let Some(feed) = &mut feed {
+        if failed {
+            tracing::warn!("some posts could not be fetched, not saving feed position");
+        } else {
+            feed.commit()?;
+        }
+    }
 
-        let post = match post {
+    Ok(())
+}
+
+/// Rebuilds saved posts from their archived HTML without touching the network.
+async fn replay(args: &cli::Args, client: &http::Client) -> anyhow::Result<()> {
+    for state in state::archived_posts(args)? {
+        let entry = state.entry();
+        let html = match state.archived_html() {
+            Ok(html) => html,
+            Err(e) => {
+                tracing::error!(?e, entry.url, "failed reading archived HTML");
+                continue;
+            }
+        };
+
+        let full_album = wants_full_album(&entry, args);
+
+        let mut post = match entry.extractor.parse_archived(&entry, &html, full_album) {
             Ok(post) => post,
-            Err(e) if single_url => anyhow::bail!(e),
             Err(e) => {
-                tracing::error!(?e, url);
+                tracing::error!(?e, entry.url, "failed rebuilding post from archived HTML");
                 continue;
             }
         };
 
-        let mut state = state::State::try_get_or_create(post, &args.download_to)?;
-        metrics::inc(metrics::TracksSeen, state.tracks.len());
+        post.apply_entry(&entry);
+        process_post(post, args, client, None).await?;
+    }
 
-        if let Some(spotify) = &spotify {
-            spotify.exec(&mut state).await?;
-        }
+    Ok(())
+}
+
+/// Tidies up after earlier runs and starts the download workers, for the
+/// commands that download.
+fn start_downloads(args: &cli::Args, client: &http::Client) {
+    match download::clean_stale_parts(&args.download_to) {
+        Ok(0) => {}
+        Ok(n) => tracing::info!("removed {n} stale partial downloads"),
+        Err(e) => tracing::warn!("failed cleaning up partial downloads: {e}"),
+    }
 
-        if !args.no_download {
-            download::download(&state).await;
-            state.save()?;
+    download::start(client, args.download_workers);
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = cli::args();
+
+    tracing_subscriber::fmt()
+        .with_writer(|| progress::Stdout)
+        .init();
+
+    let mut client = http::client(args.http_limits(), &args.http_identity())?;
+    if !args.no_cache {
+        client = client.with_cache(cache::Cache::new(&args.download_to, args.cache_ttl));
+    }
 
-            tag::tag(&state).await?;
+    match (&args.command, args.backfill) {
+        (Some(cli::Command::Watch { interval }), _) => {
+            start_downloads(&args, &client);
+            watch::watch(&args, *interval, &client).await?
+        }
+        (
+            Some(cli::Command::Cache {
+                action: cli::CacheCommand::Clear,
+            }),
+            _,
+        ) => {
+            let count = cache::clear(&args.download_to)?;
+            println!("removed {count} cached pages");
+            return Ok(());
+        }
+        (None, Some(until)) => {
+            start_downloads(&args, &client);
+            backfill(&args, until, &client).await?
+        }
+        // --offline never downloads
+        (None, None) if args.offline => replay(&args, &client).await?,
+        (None, None) => {
+            start_downloads(&args, &client);
+            scan(&args, &client).await?
         }
     }
 
+    progress::clear_screen();
+
     for (metric, value) in metrics::summarize() {
         println!(
             "{metric:width$} => {value}",
@@ -84,5 +397,18 @@ async fn main() -> anyhow::Result<()> {
         );
     }
 
+    let failing = download::failing();
+    if !failing.is_empty() {
+        println!("\npermanently failing downloads:");
+        for (path, failures) in failing {
+            println!(
+                "  {} ({} attempts): {}",
+                path.display(),
+                failures.attempts,
+                failures.last_error.as_deref().unwrap_or("unknown error")
+            );
+        }
+    }
+
     Ok(())
 }
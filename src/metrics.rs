@@ -19,13 +19,21 @@ pub(crate) enum Metric {
     BlogPostsSeen,
     SpotifyPlaylistsCreated,
     TracksDownloaded,
+    TracksQuarantined,
+    DownloadUrlsRefreshed,
     TracksDiscoveredOnSpotify,
+    TracksMatchedFromEmbeds,
     TracksMissingFromSpotify,
     TracksAddedToSpotifyPlaylist,
     TracksWithUpdatedTags,
     TracksSeen,
+    TracksEnriched,
     SpotifyTrackSearchQueries,
+    SpotifyAlbumSearchQueries,
     SpotifyErrors,
+    HttpRetries,
+    HttpCacheHits,
+    HttpCacheRevalidations,
 }
 
 type Metrics = BTreeMap<Metric, usize>;
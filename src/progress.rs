@@ -0,0 +1,329 @@
+//! Download progress, redrawn in place when stdout is a terminal and logged
+//! every so often when it isn't.
+
+use crate::types::Duration;
+use std::io::{IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+const LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Longer names are cut short so that lines don't wrap
+const NAME_WIDTH: usize = 48;
+
+/// Number of progress lines currently on screen
+static DRAWN: Mutex<usize> = Mutex::new(0);
+
+#[derive(Debug)]
+struct File {
+    id: u64,
+    name: String,
+    downloaded: u64,
+    total: Option<u64>,
+}
+
+/// Counts for the current batch, which ends whenever the queue runs dry.
+#[derive(Debug, Default)]
+struct Inner {
+    next_id: u64,
+    started: Option<Instant>,
+    queued: usize,
+    active: Vec<File>,
+    finished: usize,
+    finished_bytes: u64,
+}
+
+impl Inner {
+    fn is_idle(&self) -> bool {
+        self.queued == 0 && self.active.is_empty()
+    }
+
+    fn eta(&self, now: Instant) -> Option<Duration> {
+        let elapsed = (now - self.started?).as_secs_f64();
+        let downloaded =
+            self.finished_bytes + self.active.iter().map(|f| f.downloaded).sum::<u64>();
+        if elapsed <= 0.0 || downloaded == 0 {
+            return None;
+        }
+
+        // guess the size of files we know nothing about yet from the ones we do
+        let known: Vec<u64> = self.active.iter().filter_map(|f| f.total).collect();
+        let average = if self.finished > 0 {
+            self.finished_bytes / self.finished as u64
+        } else if !known.is_empty() {
+            known.iter().sum::<u64>() / known.len() as u64
+        } else {
+            return None;
+        };
+
+        let remaining: u64 = self
+            .active
+            .iter()
+            .map(|f| f.total.unwrap_or(average).saturating_sub(f.downloaded))
+            .sum::<u64>()
+            + average * self.queued as u64;
+
+        let rate = downloaded as f64 / elapsed;
+        Some(Duration::from_secs_f64(remaining as f64 / rate))
+    }
+
+    fn summary(&self, now: Instant) -> String {
+        let total = self.finished + self.active.len() + self.queued;
+        let mut summary = format!(
+            "downloading {} of {total} files, {} done",
+            self.active.len(),
+            self.finished,
+        );
+
+        if let Some(eta) = self.eta(now) {
+            summary.push_str(&format!(", ETA {}", format_eta(eta)));
+        }
+
+        summary
+    }
+
+    fn lines(&self, now: Instant) -> Vec<String> {
+        if self.is_idle() {
+            return Vec::new();
+        }
+
+        let mut lines: Vec<String> = self
+            .active
+            .iter()
+            .map(|f| {
+                let total = f.total.map_or("?".to_owned(), format_bytes);
+                let name: String = f.name.chars().take(NAME_WIDTH).collect();
+                format!("{:>10} / {total:<10} {name}", format_bytes(f.downloaded))
+            })
+            .collect();
+
+        lines.push(self.summary(now));
+        lines
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Progress {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Progress {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().expect("progress lock is poisoned!")
+    }
+
+    pub(crate) fn queued(&self) {
+        let mut inner = self.lock();
+        inner.started.get_or_insert_with(Instant::now);
+        inner.queued += 1;
+    }
+
+    pub(crate) fn start(&self, name: &str) -> FileProgress {
+        let mut inner = self.lock();
+        inner.queued = inner.queued.saturating_sub(1);
+
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.active.push(File {
+            id,
+            name: name.to_owned(),
+            downloaded: 0,
+            total: None,
+        });
+
+        FileProgress {
+            progress: self.clone(),
+            id,
+            finished: false,
+        }
+    }
+
+    /// Redraws or logs progress until the program exits.
+    pub(crate) fn spawn(&self) {
+        let progress = self.clone();
+        let tty = std::io::stdout().is_terminal();
+
+        tokio::spawn(async move {
+            let interval = if tty { REDRAW_INTERVAL } else { LOG_INTERVAL };
+
+            loop {
+                tokio::time::sleep(interval).await;
+
+                let now = Instant::now();
+                if tty {
+                    draw(&progress.lock().lines(now));
+                } else {
+                    let inner = progress.lock();
+                    if !inner.is_idle() {
+                        tracing::info!("{}", inner.summary(now));
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// A single file being downloaded, which drops out of the display when
+/// this is dropped.
+#[derive(Debug)]
+pub(crate) struct FileProgress {
+    progress: Progress,
+    id: u64,
+    finished: bool,
+}
+
+impl FileProgress {
+    fn update(&self, f: impl FnOnce(&mut File)) {
+        let mut inner = self.progress.lock();
+        if let Some(file) = inner.active.iter_mut().find(|file| file.id == self.id) {
+            f(file);
+        }
+    }
+
+    pub(crate) fn set_total(&self, total: Option<u64>) {
+        self.update(|file| file.total = total);
+    }
+
+    pub(crate) fn add(&self, bytes: u64) {
+        self.update(|file| file.downloaded += bytes);
+    }
+
+    pub(crate) fn finish(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for FileProgress {
+    fn drop(&mut self) {
+        let mut inner = self.progress.lock();
+
+        if let Some(i) = inner.active.iter().position(|file| file.id == self.id) {
+            let file = inner.active.swap_remove(i);
+            if self.finished {
+                inner.finished += 1;
+                inner.finished_bytes += file.downloaded;
+            }
+        }
+
+        if inner.is_idle() {
+            *inner = Inner {
+                next_id: inner.next_id,
+                ..Inner::default()
+            };
+        }
+    }
+}
+
+fn clear(out: &mut impl Write, drawn: &mut usize) -> std::io::Result<()> {
+    if *drawn > 0 {
+        write!(out, "\x1b[{drawn}A\x1b[J")?;
+        *drawn = 0;
+    }
+
+    Ok(())
+}
+
+fn draw(lines: &[String]) {
+    let mut drawn = DRAWN.lock().expect("progress lock is poisoned!");
+    let mut out = std::io::stdout().lock();
+
+    let res = clear(&mut out, &mut drawn)
+        .and_then(|_| lines.iter().try_for_each(|line| writeln!(out, "{line}")))
+        .and_then(|_| out.flush());
+
+    if res.is_ok() {
+        *drawn = lines.len();
+    }
+}
+
+/// Removes any progress from the screen.
+pub(crate) fn clear_screen() {
+    draw(&[]);
+}
+
+/// Stdout for log output, which clears the progress display before writing
+/// so that the two don't get tangled up. It's redrawn on the next tick.
+pub(crate) struct Stdout;
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut drawn = DRAWN.lock().expect("progress lock is poisoned!");
+        let mut out = std::io::stdout().lock();
+        clear(&mut out, &mut drawn)?;
+        out.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stdout().flush()
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+fn format_eta(eta: Duration) -> String {
+    let secs = eta.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m{s:02}s"),
+        (h, m, _) => format!("{h}h{m:02}m"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatting() {
+        assert_eq!("512 B", format_bytes(512));
+        assert_eq!("1.5 KiB", format_bytes(1536));
+        assert_eq!("12.0 MiB", format_bytes(12 * 1024 * 1024));
+
+        assert_eq!("42s", format_eta(Duration::from_secs(42)));
+        assert_eq!("3m05s", format_eta(Duration::from_secs(185)));
+        assert_eq!("2h01m", format_eta(Duration::from_secs(7260)));
+    }
+
+    #[test]
+    fn eta() {
+        let progress = Progress::default();
+        for _ in 0..3 {
+            progress.queued();
+        }
+
+        let file = progress.start("one");
+        file.set_total(Some(100));
+        file.add(100);
+        file.finish();
+
+        let mut inner = progress.lock();
+        let started = Instant::now() - Duration::from_secs(10);
+        inner.started = Some(started);
+
+        // 100 bytes in 10s, with two more files of about 100 bytes to go
+        assert_eq!(
+            Some(Duration::from_secs(20)),
+            inner.eta(started + Duration::from_secs(10))
+        );
+        assert_eq!(
+            "downloading 0 of 3 files, 1 done, ETA 20s",
+            inner.summary(started + Duration::from_secs(10))
+        );
+    }
+}
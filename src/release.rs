@@ -0,0 +1,405 @@
+//! Bandcamp album, track, artist and label pages given directly with `--url`,
+//! wrapped up as a [`BlogPost`] so they go through the usual pipeline.
+
+use crate::bandcamp::{selector, BlogMeta, BlogPost, PlayerData, TrackInfo, TrackList};
+use crate::feed::Entry;
+use crate::tralbum::TRALBUM_DATA;
+use crate::types::{DateTime, Duration};
+use anyhow::Context;
+use scraper::Html;
+use serde_json as json;
+use std::collections::BTreeMap;
+
+const DOMAIN: &str = ".bandcamp.com";
+
+/// Subdomains of bandcamp.com that don't belong to an artist or label
+const NON_ARTIST_SUBDOMAINS: &[&str] = &["daily", "www", "f4", "t4"];
+
+selector!(EMBED_DATA, "[data-embed]");
+selector!(BAND_DATA, "[data-band]");
+selector!(MUSIC_GRID, "#music-grid");
+selector!(GRID_LINK, "#music-grid li a[href]");
+
+/// What kind of Bandcamp page a URL points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Page {
+    /// An `/album/...` or `/track/...` page
+    Release,
+
+    /// An artist or label front page, listing their releases
+    Discography,
+}
+
+/// `(origin, path)` of a URL on an artist or label subdomain
+fn split_url(url: &str) -> Option<(&str, &str)> {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+
+    let host_len = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (host, path) = rest.split_at(host_len);
+    let subdomain = host.strip_suffix(DOMAIN)?;
+
+    if subdomain.is_empty() || subdomain.contains('.') || NON_ARTIST_SUBDOMAINS.contains(&subdomain)
+    {
+        return None;
+    }
+
+    let origin = &url[..url.len() - rest.len() + host_len];
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    Some((origin, path))
+}
+
+impl Page {
+    pub(crate) fn from_url(url: &str) -> Option<Self> {
+        let (_, path) = split_url(url)?;
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+        match segments[..] {
+            ["album" | "track", _] => Some(Self::Release),
+            [] | ["music"] => Some(Self::Discography),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ReleaseTrack {
+    #[serde(alias = "track_id")]
+    id: Option<u64>,
+    title: String,
+    artist: Option<String>,
+    track_num: Option<usize>,
+    #[serde(default, deserialize_with = "crate::util::duration_from_f64")]
+    duration: Duration,
+    #[serde(default)]
+    file: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct Current {
+    title: Option<String>,
+    release_date: Option<String>,
+    band_id: Option<u64>,
+}
+
+/// The `data-tralbum` blob from an album or track page
+#[derive(Debug, serde::Deserialize)]
+struct ReleaseData {
+    id: Option<u64>,
+    item_type: String,
+    artist: String,
+    url: Option<String>,
+    #[serde(default)]
+    art_id: Option<u64>,
+    #[serde(default)]
+    current: Current,
+    #[serde(default)]
+    album_release_date: Option<String>,
+    trackinfo: Vec<ReleaseTrack>,
+}
+
+/// The parts of the `data-embed` blob that `data-tralbum` lacks
+#[derive(Debug, Default, serde::Deserialize)]
+struct EmbedData {
+    album_title: Option<String>,
+}
+
+/// A single album or track, ready to be turned into tracks
+#[derive(Debug)]
+pub(crate) struct Release {
+    pub(crate) player_data: PlayerData,
+    pub(crate) released: Option<DateTime>,
+    pub(crate) is_album: bool,
+    raw: json::Value,
+
+    /// When the page was fetched, if it was
+    fetched: Option<DateTime>,
+}
+
+impl Release {
+    pub(crate) fn from_html(html: &str, url: &str) -> anyhow::Result<Self> {
+        let doc = Html::parse_document(html);
+
+        let raw: json::Value = TRALBUM_DATA
+            .select(&doc)
+            .find_map(|elem| elem.attr("data-tralbum"))
+            .ok_or_else(|| anyhow::format_err!("no 'data-tralbum' found in page"))
+            .and_then(|data| json::from_str(data).context("parsing 'data-tralbum' as JSON"))?;
+
+        let data: ReleaseData = json::from_value(raw.clone()).context(format!(
+            "parsing {} from JSON",
+            std::any::type_name::<ReleaseData>()
+        ))?;
+
+        let embed: EmbedData = EMBED_DATA
+            .select(&doc)
+            .find_map(|elem| elem.attr("data-embed"))
+            .and_then(|data| json::from_str(data).ok())
+            .unwrap_or_default();
+
+        let is_album = data.item_type == "album";
+        let title = data.current.title.clone().unwrap_or_default();
+
+        let released = data
+            .current
+            .release_date
+            .as_deref()
+            .or(data.album_release_date.as_deref())
+            .and_then(crate::tralbum::parse_date);
+
+        let album_id = if is_album { data.id } else { None };
+        let tracklist: Vec<TrackInfo> = data
+            .trackinfo
+            .into_iter()
+            .enumerate()
+            .map(|(i, t)| TrackInfo {
+                artist: t.artist.unwrap_or_else(|| data.artist.clone()),
+                audio_track_duration: t.duration,
+                track_number: t.track_num.unwrap_or(i + 1),
+                track_title: t.title,
+                audio_url: t.file.unwrap_or_default(),
+                album_id,
+                track_id: t.id,
+            })
+            .collect();
+
+        let featured_track_number = tracklist.first().map_or(1, |t| t.track_number);
+        let tralbum_url = data.url.unwrap_or_else(|| url.to_owned());
+
+        let player_data = PlayerData {
+            title: if is_album {
+                title
+            } else {
+                embed.album_title.unwrap_or(title)
+            },
+            tracklist,
+            featured_track_number,
+            band_name: data.artist,
+            band_id: data.current.band_id,
+            band_location: None,
+            band_url: split_url(&tralbum_url).map(|(origin, _)| origin.to_owned()),
+            tralbum_url: Some(tralbum_url),
+            art_id: data.art_id,
+        };
+
+        Ok(Self {
+            player_data,
+            released,
+            is_album,
+            raw,
+            fetched: None,
+        })
+    }
+
+    pub(crate) async fn try_from_url(
+        url: &str,
+        client: &crate::http::Client,
+    ) -> anyhow::Result<Self> {
+        let (html, fetched) = client.fetch_text_dated(url).await?;
+        Ok(Self {
+            fetched: Some(fetched),
+            ..Self::from_html(&html, url)?
+        })
+    }
+}
+
+/// Release URLs listed on an artist or label page, in page order. Releases
+/// beyond the first screenful are only listed in `data-client-items`.
+pub(crate) fn discography_urls(html: &str, page_url: &str) -> Vec<String> {
+    let doc = Html::parse_document(html);
+    let origin = split_url(page_url).map_or(page_url, |(origin, _)| origin);
+
+    let hrefs = GRID_LINK
+        .select(&doc)
+        .filter_map(|elem| elem.attr("href"))
+        .map(str::to_owned);
+
+    let client_items: Vec<String> = MUSIC_GRID
+        .select(&doc)
+        .filter_map(|elem| elem.attr("data-client-items"))
+        .filter_map(|data| json::from_str::<Vec<json::Value>>(data).ok())
+        .flatten()
+        .filter_map(|item| item["page_url"].as_str().map(str::to_owned))
+        .collect();
+
+    let mut urls: Vec<String> = Vec::new();
+    for href in hrefs.chain(client_items) {
+        let url = if href.starts_with('/') {
+            format!("{origin}{href}")
+        } else {
+            href
+        };
+
+        // label pages link to releases on the artist's own subdomain
+        let url = url.split(['?', '#']).next().unwrap_or_default().to_owned();
+
+        if Page::from_url(&url) == Some(Page::Release) && !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+
+    urls
+}
+
+fn band_name(doc: &Html) -> Option<String> {
+    BAND_DATA
+        .select(doc)
+        .find_map(|elem| elem.attr("data-band"))
+        .and_then(|data| json::from_str::<json::Value>(data).ok())
+        .and_then(|band| band["name"].as_str().map(str::to_owned))
+}
+
+/// Builds a [`BlogPost`] from a Bandcamp release, artist or label page.
+/// Albums are always fetched in full.
+pub(crate) async fn blog_post(
+    entry: &Entry,
+    client: &crate::http::Client,
+) -> anyhow::Result<BlogPost> {
+    let (html, fetched) = client.fetch_text_dated(&entry.url).await?;
+
+    // artists with a single release get that release as their front page
+    let (meta, band, urls) = {
+        let doc = Html::parse_document(&html);
+        let meta = BlogMeta::from_generic_html(&doc, entry)?;
+
+        if TRALBUM_DATA.select(&doc).next().is_some() {
+            (meta, None, vec![])
+        } else {
+            (meta, band_name(&doc), discography_urls(&html, &entry.url))
+        }
+    };
+
+    let mut releases = vec![];
+    if urls.is_empty() {
+        releases.push(Release::from_html(&html, &entry.url)?);
+    } else {
+        for url in urls {
+            match Release::try_from_url(&url, client).await {
+                Ok(release) => releases.push(release),
+                Err(e) => tracing::warn!(url, "failed fetching release: {e}"),
+            }
+        }
+    }
+
+    let fetched = releases
+        .iter()
+        .filter_map(|release| release.fetched)
+        .chain([fetched])
+        .min();
+
+    let mut post = build_post(meta, band, releases)?;
+    post.html = Some(html);
+    post.fetched = fetched;
+    Ok(post)
+}
+
+/// Rebuilds a single release page from its archived HTML. Discographies link
+/// out to other pages, so they can't be rebuilt this way.
+pub(crate) fn from_archive(entry: &Entry, html: &str) -> anyhow::Result<BlogPost> {
+    let meta = BlogMeta::from_generic_html(&Html::parse_document(html), entry)?;
+    let release = Release::from_html(html, &entry.url)
+        .context("only single release pages can be rebuilt offline")?;
+
+    build_post(meta, None, vec![release])
+}
+
+fn build_post(
+    meta: BlogMeta,
+    band: Option<String>,
+    releases: Vec<Release>,
+) -> anyhow::Result<BlogPost> {
+    if releases.is_empty() {
+        anyhow::bail!("no releases found");
+    }
+
+    let title = match (&band, &releases[..]) {
+        (Some(band), _) => band.clone(),
+        (None, [release]) => format!(
+            "{} - {}",
+            release.player_data.band_name, release.player_data.title
+        ),
+        (None, _) => meta.title.clone(),
+    };
+
+    // the date names the post directory, so a discography has to keep the
+    // page's date rather than move whenever a new release shows up
+    let published = match &releases[..] {
+        [release] if band.is_none() => release.released.unwrap_or(meta.published),
+        _ => meta.published,
+    };
+
+    let mut list = TrackList::default();
+    for (idx, release) in releases.into_iter().enumerate() {
+        list.tracks
+            .extend(release.player_data.get_tracks(idx + 1, release.is_album));
+        list.raw.push(release.raw);
+    }
+
+    Ok(BlogPost::new(
+        BlogMeta {
+            title,
+            published,
+            modified: published,
+            ..meta
+        },
+        list,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn release_from_html() {
+        let html = r#"<html><head>
+<script data-tralbum="{&quot;id&quot;:42,&quot;item_type&quot;:&quot;album&quot;,&quot;artist&quot;:&quot;Some Artist&quot;,&quot;url&quot;:&quot;https://artist.bandcamp.com/album/some-album&quot;,&quot;art_id&quot;:123,&quot;current&quot;:{&quot;title&quot;:&quot;Some Album&quot;,&quot;release_date&quot;:&quot;05 Jan 2024 00:00:00 GMT&quot;,&quot;band_id&quot;:7},&quot;trackinfo&quot;:[{&quot;track_id&quot;:1,&quot;title&quot;:&quot;First&quot;,&quot;artist&quot;:null,&quot;track_num&quot;:1,&quot;duration&quot;:61.5,&quot;file&quot;:{&quot;mp3-128&quot;:&quot;https://t4.bcbits.com/stream/1&quot;}},{&quot;track_id&quot;:2,&quot;title&quot;:&quot;Second&quot;,&quot;artist&quot;:&quot;Guest&quot;,&quot;track_num&quot;:2,&quot;duration&quot;:120.0,&quot;file&quot;:null}]}"></script>
+</head><body></body></html>"#;
+
+        let release =
+            Release::from_html(html, "https://artist.bandcamp.com/album/some-album").unwrap();
+
+        assert!(release.is_album);
+        assert_eq!(
+            Some("2024-01-05T00:00:00Z".parse().unwrap()),
+            release.released
+        );
+
+        let tracks = release.player_data.get_tracks(1, true);
+        assert_eq!(2, tracks.len());
+        assert_eq!("Some Album", tracks[0].album.title);
+        assert_eq!("Some Artist", tracks[0].artist.name);
+        assert_eq!(
+            Some("https://artist.bandcamp.com"),
+            tracks[0].album_artist.bandcamp_url.as_deref()
+        );
+        assert_eq!(
+            Some("https://t4.bcbits.com/stream/1"),
+            tracks[0].download_url.as_deref()
+        );
+        assert_eq!("Guest", tracks[1].artist.name);
+        assert_eq!(None, tracks[1].download_url);
+    }
+
+    #[test]
+    fn discography() {
+        let html = r#"<html><body>
+<ol id="music-grid" data-client-items="[{&quot;page_url&quot;:&quot;/album/third&quot;}]">
+  <li><a href="/album/first"><p>First</p></a></li>
+  <li><a href="https://other.bandcamp.com/track/second?label=1&amp;tab=music"><p>Second</p></a></li>
+  <li><a href="/album/first"><p>First again</p></a></li>
+  <li><a href="https://daily.bandcamp.com/features/some-post"><p>Feature</p></a></li>
+</ol>
+</body></html>"#;
+
+        assert_eq!(
+            vec![
+                "https://label.bandcamp.com/album/first".to_string(),
+                "https://other.bandcamp.com/track/second".to_string(),
+                "https://label.bandcamp.com/album/third".to_string(),
+            ],
+            discography_urls(html, "https://label.bandcamp.com/music")
+        );
+    }
+}
@@ -4,6 +4,7 @@ use fuzzt::algorithms::jaro;
 use unicode_normalization::UnicodeNormalization;
 
 use crate::types::{self, SpotifyTrack};
+use rspotify::model::{SimplifiedAlbum, SimplifiedTrack};
 
 const TITLE_WEIGHT: f64 = 100.0;
 const ARTIST_WEIGHT: f64 = 50.0;
@@ -404,6 +405,62 @@ impl<'a> TrackMatcher<'a> {
     }
 }
 
+/// Matches a Bandcamp release against Spotify albums, and the release's
+/// tracks against the Spotify album's tracks.
+#[derive(Debug)]
+pub(crate) struct AlbumMatcher {
+    title: StringMatcher<Album>,
+    artist: StringMatcher<Artist>,
+}
+
+impl AlbumMatcher {
+    pub(crate) fn new(album: &types::Album, artist: &types::Artist) -> Self {
+        Self {
+            title: StringMatcher::new(&album.title),
+            artist: StringMatcher::new(&artist.name),
+        }
+    }
+
+    pub(crate) fn score(&mut self, result: &SimplifiedAlbum) -> Option<u64> {
+        let title = self.title.score(&result.name);
+        let artist = result
+            .artists
+            .iter()
+            .map(|a| self.artist.score(&a.name))
+            .fold(0.0, f64::max);
+
+        if title < MIN_TITLE_SCORE || artist < MIN_ARTIST_SCORE {
+            return None;
+        }
+
+        let score =
+            ((title * TITLE_WEIGHT) + (artist * ARTIST_WEIGHT)) / (TITLE_WEIGHT + ARTIST_WEIGHT);
+        Some(score.floor() as u64)
+    }
+
+    /// Picks the album track with the closest title, preferring one with the
+    /// same track number on ties.
+    pub(crate) fn match_track<'a>(
+        track: &types::Track,
+        candidates: &'a [SimplifiedTrack],
+    ) -> Option<&'a SimplifiedTrack> {
+        let mut title = StringMatcher::<TrackTitle>::new(&track.title);
+
+        candidates
+            .iter()
+            .map(|candidate| {
+                let mut score = title.score(&candidate.name);
+                if candidate.track_number as usize == track.number {
+                    score += 1.0;
+                }
+                (score, candidate)
+            })
+            .filter(|(score, _)| *score >= MIN_TITLE_SCORE)
+            .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+            .map(|(_, candidate)| candidate)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
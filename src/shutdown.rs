@@ -0,0 +1,60 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::LazyLock;
+use tokio::sync::Notify;
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+static NOTIFY: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+#[cfg(unix)]
+fn terminated() -> anyhow::Result<impl std::future::Future<Output = ()>> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = signal(SignalKind::terminate())?;
+    Ok(async move {
+        sigterm.recv().await;
+    })
+}
+
+/// There's no SIGTERM here, so only Ctrl-C counts
+#[cfg(not(unix))]
+fn terminated() -> anyhow::Result<impl std::future::Future<Output = ()>> {
+    Ok(std::future::pending())
+}
+
+/// Spawns a task that flags a shutdown on SIGTERM or Ctrl-C.
+pub(crate) fn listen() -> anyhow::Result<()> {
+    let terminated = terminated()?;
+
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = terminated => {},
+            _ = tokio::signal::ctrl_c() => {},
+        }
+
+        tracing::info!("shutdown requested, finishing in-flight work");
+        REQUESTED.store(true, Ordering::SeqCst);
+        NOTIFY.notify_waiters();
+    });
+
+    Ok(())
+}
+
+pub(crate) fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Sleeps for `duration`, returning early if a shutdown is requested.
+pub(crate) async fn sleep(duration: std::time::Duration) {
+    let notified = NOTIFY.notified();
+    tokio::pin!(notified);
+    notified.as_mut().enable();
+
+    if requested() {
+        return;
+    }
+
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => {},
+        _ = notified => {},
+    }
+}
@@ -0,0 +1,223 @@
+use crate::bandcamp::{self, selector, BlogPost};
+use crate::feed::Entry;
+use scraper::Html;
+use std::str::FromStr;
+
+/// How to turn a post from a feed into a [`BlogPost`].
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    strum::Display,
+    strum::EnumString,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub(crate) enum Extractor {
+    /// Bandcamp Daily articles, using the player data embedded in the page
+    #[default]
+    BandcampDaily,
+
+    /// Any page with `bandcamp.com/EmbeddedPlayer` iframes
+    EmbeddedPlayer,
+
+    /// A Bandcamp album, track, artist or label page
+    BandcampRelease,
+
+    /// A Bandcamp fan's collection or wishlist
+    BandcampFan,
+}
+
+impl Extractor {
+    fn for_url(url: &str) -> Self {
+        if url.starts_with(bandcamp::BASE_URL) {
+            Self::BandcampDaily
+        } else {
+            Self::EmbeddedPlayer
+        }
+    }
+
+    /// Picks the extractor for a single post given with `--url`.
+    pub(crate) fn for_post_url(url: &str) -> Self {
+        if crate::release::Page::from_url(url).is_some() {
+            Self::BandcampRelease
+        } else if crate::fan::List::from_url(url).is_some() {
+            Self::BandcampFan
+        } else {
+            Self::for_url(url)
+        }
+    }
+
+    pub(crate) async fn fetch_post(
+        &self,
+        entry: &Entry,
+        client: &crate::http::Client,
+        full_album: bool,
+    ) -> anyhow::Result<BlogPost> {
+        let mut post = match self {
+            Self::BandcampDaily => BlogPost::try_from_url(&entry.url, client, full_album).await?,
+            Self::EmbeddedPlayer => crate::embed::blog_post(entry, client, full_album).await?,
+            Self::BandcampRelease => crate::release::blog_post(entry, client).await?,
+            Self::BandcampFan => crate::fan::blog_post(entry, client).await?,
+        };
+
+        post.extractor = *self;
+        Ok(post)
+    }
+
+    /// Rebuilds a post from its archived HTML, for extractors that don't need
+    /// anything beyond the page itself.
+    pub(crate) fn parse_archived(
+        &self,
+        entry: &Entry,
+        html: &str,
+        full_album: bool,
+    ) -> anyhow::Result<BlogPost> {
+        let mut post = match self {
+            Self::BandcampDaily => BlogPost::from_html(html, full_album)?,
+            Self::BandcampRelease => crate::release::from_archive(entry, html)?,
+            Self::EmbeddedPlayer | Self::BandcampFan => {
+                anyhow::bail!("{self} posts can't be rebuilt without the network")
+            }
+        };
+
+        post.extractor = *self;
+        Ok(post)
+    }
+}
+
+/// A feed to scan for posts.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Source {
+    pub(crate) feed_url: String,
+    pub(crate) extractor: Extractor,
+}
+
+impl Source {
+    pub(crate) fn bandcamp_daily() -> Self {
+        Self {
+            feed_url: bandcamp::FEED_URL.to_string(),
+            extractor: Extractor::BandcampDaily,
+        }
+    }
+}
+
+impl FromStr for Source {
+    type Err = anyhow::Error;
+
+    /// Parses `URL` or `EXTRACTOR:URL`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((name, url)) = s.split_once(':') {
+            if let Ok(extractor) = name.parse() {
+                return Ok(Self {
+                    feed_url: url.to_string(),
+                    extractor,
+                });
+            }
+        }
+
+        if !s.starts_with("http://") && !s.starts_with("https://") {
+            anyhow::bail!("invalid feed url: '{s}'");
+        }
+
+        Ok(Self {
+            feed_url: s.to_string(),
+            extractor: Extractor::for_url(s),
+        })
+    }
+}
+
+selector!(OPML_OUTLINE, "outline[xmlurl]");
+
+/// Reads feed URLs from an OPML subscription list. An outline may pick its
+/// extractor with an `extractor="..."` attribute.
+pub(crate) fn from_opml(xml: &str) -> anyhow::Result<Vec<Source>> {
+    // html5ever lowercases attribute names, hence `xmlurl` instead of `xmlUrl`
+    OPML_OUTLINE
+        .select(&Html::parse_document(xml))
+        .filter_map(|outline| {
+            let url = outline.attr("xmlurl")?;
+            Some(match outline.attr("extractor") {
+                Some(name) => name
+                    .parse()
+                    .map(|extractor| Source {
+                        feed_url: url.to_string(),
+                        extractor,
+                    })
+                    .map_err(|e| anyhow::format_err!("invalid extractor '{name}': {e}")),
+                None => url.parse(),
+            })
+        })
+        .collect()
+}
+
+/// The feeds selected on the command line, defaulting to Bandcamp Daily.
+pub(crate) fn sources(args: &crate::cli::Args) -> anyhow::Result<Vec<Source>> {
+    let mut sources = args.feeds.clone();
+
+    if let Some(path) = &args.opml {
+        let xml = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::format_err!("reading OPML file {path}: {e}"))?;
+        sources.extend(from_opml(&xml)?);
+    }
+
+    if sources.is_empty() {
+        sources.push(Source::bandcamp_daily());
+    }
+
+    Ok(sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_source() {
+        let source: Source = "https://daily.bandcamp.com/feed/".parse().unwrap();
+        assert_eq!(Source::bandcamp_daily(), source);
+
+        let source: Source = "https://example.com/rss".parse().unwrap();
+        assert_eq!(Extractor::EmbeddedPlayer, source.extractor);
+
+        let source: Source = "bandcamp-daily:https://example.com/rss".parse().unwrap();
+        assert_eq!(Extractor::BandcampDaily, source.extractor);
+        assert_eq!("https://example.com/rss", source.feed_url);
+
+        assert!("example.com/rss".parse::<Source>().is_err());
+    }
+
+    #[test]
+    fn parse_opml() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="2.0">
+  <head><title>feeds</title></head>
+  <body>
+    <outline text="music">
+      <outline type="rss" text="A" xmlUrl="https://a.example.com/feed"/>
+      <outline type="rss" text="B" xmlUrl="https://b.example.com/feed" extractor="bandcamp-daily"/>
+    </outline>
+  </body>
+</opml>"#;
+
+        let sources = from_opml(xml).unwrap();
+        assert_eq!(
+            vec![
+                Source {
+                    feed_url: "https://a.example.com/feed".to_string(),
+                    extractor: Extractor::EmbeddedPlayer,
+                },
+                Source {
+                    feed_url: "https://b.example.com/feed".to_string(),
+                    extractor: Extractor::BandcampDaily,
+                },
+            ],
+            sources
+        );
+    }
+}
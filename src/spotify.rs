@@ -1,13 +1,14 @@
 use anyhow::Context;
 use futures::stream::TryStreamExt;
 use rspotify::model::{
-    Country, Market, PlayableId, PlaylistId, SearchResult, SearchType, TrackId, UserId,
+    Country, Market, PlayableId, PlaylistId, SearchResult, SearchType, SimplifiedTrack, TrackId,
+    UserId,
 };
 use rspotify::prelude::*;
 use rspotify::{AuthCodeSpotify, Credentials};
 
 use crate::metrics;
-use crate::search::TrackMatcher;
+use crate::search::{AlbumMatcher, TrackMatcher};
 use crate::state::State;
 use crate::types;
 
@@ -15,11 +16,57 @@ use crate::types;
 pub(crate) struct Client {
     spotify: AuthCodeSpotify,
     user: UserId<'static>,
+    playlist_name: String,
 }
 
 const MARKET: Market = Market::Country(Country::UnitedStates);
 
-pub(crate) async fn connect() -> anyhow::Result<Client> {
+pub(crate) const DEFAULT_PLAYLIST_NAME: &str = "Bandcamp - {date} - {title}";
+
+/// Placeholders allowed in `--playlist-name`
+const PLAYLIST_NAME_FIELDS: &[&str] = &["date", "title", "author", "series", "tags"];
+
+/// Checks that a `--playlist-name` template only uses known placeholders.
+pub(crate) fn parse_playlist_name(s: &str) -> Result<String, String> {
+    let mut rest = s;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            return Err("unclosed '{' in playlist name".to_string());
+        };
+
+        let field = &rest[start + 1..start + len];
+        if !PLAYLIST_NAME_FIELDS.contains(&field) {
+            return Err(format!(
+                "unknown placeholder '{{{field}}}', expected one of: {}",
+                PLAYLIST_NAME_FIELDS.join(", ")
+            ));
+        }
+
+        rest = &rest[start + len + 1..];
+    }
+
+    Ok(s.to_string())
+}
+
+fn playlist_name(template: &str, post: &crate::bandcamp::BlogPost) -> String {
+    let name = template
+        .replace("{date}", &post.published.format("%Y-%m-%d").to_string())
+        .replace("{title}", &post.title)
+        .replace("{author}", post.author.as_deref().unwrap_or_default())
+        .replace("{series}", post.series.as_deref().unwrap_or_default())
+        .replace("{tags}", &post.tags.join(", "));
+
+    // tidy up after empty placeholders
+    name.replace(" -  - ", " - ")
+        .trim_matches(|c: char| c == '-' || c.is_whitespace())
+        .to_string()
+}
+
+/// Logs in to Spotify.
+///
+/// rspotify builds its own HTTP client, so `--proxy`, `--user-agent` and
+/// `--header` don't apply here; only the usual proxy environment variables do.
+pub(crate) async fn connect(playlist_name: &str) -> anyhow::Result<Client> {
     let config = rspotify::Config {
         token_cached: true,
         token_refreshing: true,
@@ -54,10 +101,23 @@ pub(crate) async fn connect() -> anyhow::Result<Client> {
 
     spotify.write_token_cache().await?;
 
-    Ok(Client { spotify, user })
+    Ok(Client {
+        spotify,
+        user,
+        playlist_name: playlist_name.to_string(),
+    })
 }
 
 impl Client {
+    /// Refreshes the access token if it has expired, so that a long-running
+    /// process doesn't have to prompt for a new one.
+    pub(crate) async fn refresh_token(&self) -> anyhow::Result<()> {
+        self.spotify
+            .auto_reauth()
+            .await
+            .context("refreshing Spotify token")
+    }
+
     pub(crate) async fn get_or_create_playlist(&self, state: &mut State) -> anyhow::Result<()> {
         if !state.has_spotify_tracks() {
             tracing::debug!(
@@ -75,11 +135,7 @@ impl Client {
             return Ok(());
         }
 
-        let title = format!(
-            "Bandcamp - {} - {}",
-            state.blog_info.published.format("%Y-%m-%d"),
-            &state.blog_info.title
-        );
+        let title = playlist_name(&self.playlist_name, &state.blog_info);
 
         tracing::debug!(name = &title, "searching for playlist");
 
@@ -218,9 +274,205 @@ impl Client {
         Ok(())
     }
 
+    /// Finds the Spotify album for a release captured in full-album mode and
+    /// assigns its tracks to ours.
+    async fn search_album(&self, tracks: &mut [types::Track]) -> anyhow::Result<()> {
+        let Some(first) = tracks.first() else {
+            return Ok(());
+        };
+
+        let album = first.album.clone();
+        let artist = first.album_artist.clone();
+
+        // something isn't properly urlencoding `%` in the query string :(
+        let query = format!(
+            "album:{} artist:{}",
+            album.title.replace("%", "%25"),
+            artist.name.replace("%", "%25")
+        );
+
+        metrics::inc(metrics::SpotifyAlbumSearchQueries, 1);
+
+        let result = self
+            .spotify
+            .search(
+                &query,
+                SearchType::Album,
+                Some(MARKET),
+                None,
+                Some(10),
+                None,
+            )
+            .await
+            .with_context(|| format!("searching album: {}", album.title))?;
+
+        let SearchResult::Albums(albums) = result else {
+            tracing::warn!(album = album.title, "unexpected album search results");
+            return Ok(());
+        };
+
+        let mut matcher = AlbumMatcher::new(&album, &artist);
+        let best = albums
+            .items
+            .iter()
+            .filter_map(|result| Some((matcher.score(result)?, result)))
+            .max_by(|(score_a, _), (score_b, _)| score_a.cmp(score_b));
+
+        let Some((score, best)) = best else {
+            tracing::info!(
+                "no match for album('{}') out of {} results from Spotify",
+                album.title,
+                albums.items.len()
+            );
+            return Ok(());
+        };
+
+        let Some(ref album_id) = best.id else {
+            anyhow::bail!("Album: {best:?} does not have an ID");
+        };
+
+        tracing::info!(
+            "Result album: {}, artist: {}, score: {}",
+            best.name,
+            best.artists.first().map(|a| a.name.as_str()).unwrap_or(""),
+            score
+        );
+
+        let candidates: Vec<SimplifiedTrack> = self
+            .spotify
+            .album_track(album_id.clone(), Some(MARKET))
+            .try_collect()
+            .await
+            .context("fetching album tracks")?;
+
+        for track in tracks.iter_mut() {
+            track.album.spotify_id = Some(album_id.to_string());
+
+            if track.spotify_id.is_some() {
+                continue;
+            }
+
+            let Some(id) =
+                AlbumMatcher::match_track(track, &candidates).and_then(|c| c.id.as_ref())
+            else {
+                continue;
+            };
+
+            tracing::info!(track = track.title, "setting spotify id to {}", id);
+            track.spotify_id = Some(id.to_string());
+            metrics::inc(metrics::TracksDiscoveredOnSpotify, 1);
+        }
+
+        Ok(())
+    }
+
+    /// Matches Spotify tracks embedded in the post to our tracks, so they
+    /// don't have to be searched for.
+    async fn match_embeds(&self, state: &mut State) -> anyhow::Result<bool> {
+        let mut changed = false;
+
+        let ids: Vec<String> = state
+            .blog_info
+            .external_media
+            .iter()
+            .filter_map(|media| media.spotify_track_id())
+            .map(str::to_owned)
+            .collect();
+
+        let assigned = |state: &State, id: &str| {
+            state
+                .tracks
+                .iter()
+                .any(|track| track.spotify_id.as_deref() == Some(id))
+        };
+
+        for id in ids {
+            let track_id = TrackId::from_id(&id)?;
+
+            // matched on an earlier run, no need to ask Spotify again
+            if assigned(state, &track_id.to_string()) {
+                continue;
+            }
+
+            let result = self
+                .spotify
+                .track(track_id, Some(MARKET))
+                .await
+                .with_context(|| format!("fetching embedded Spotify track {id}"))?;
+
+            let Some(result_id) = &result.id else {
+                continue;
+            };
+            let result_id = result_id.to_string();
+
+            // Spotify may answer with a relinked track
+            if assigned(state, &result_id) {
+                continue;
+            }
+
+            let best = state
+                .tracks
+                .iter_mut()
+                .filter(|track| track.spotify_id.is_none())
+                .filter_map(|track| {
+                    let score = TrackMatcher::new(track).ok()?.score(&result)?;
+                    Some((score, track))
+                })
+                .max_by_key(|(score, _)| *score);
+
+            let Some((score, track)) = best else {
+                tracing::debug!(id, result.name, "embedded Spotify track matches no track");
+                continue;
+            };
+
+            tracing::info!(
+                track.title,
+                score,
+                "using embedded Spotify track {}",
+                result_id
+            );
+            track.spotify_id = Some(result_id);
+            metrics::inc(metrics::TracksMatchedFromEmbeds, 1);
+            changed = true;
+        }
+
+        Ok(changed)
+    }
+
     pub(crate) async fn exec(&self, state: &mut State) -> anyhow::Result<()> {
         let mut changed = false;
 
+        match self.match_embeds(state).await {
+            Ok(matched) => changed |= matched,
+            Err(e) => {
+                tracing::error!(error = ?e, "failed to match embedded Spotify tracks");
+                metrics::inc(metrics::SpotifyErrors, 1);
+            }
+        }
+
+        // add whole releases at once, leaving any stragglers to the
+        // per-track search below
+        for release in state.tracks.chunk_by_mut(|a, b| {
+            a.full_album
+                && b.full_album
+                && a.bandcamp_playlist_track_number == b.bandcamp_playlist_track_number
+        }) {
+            if !release[0].full_album || release.iter().all(|t| t.spotify_id.is_some()) {
+                continue;
+            }
+
+            let before: Vec<_> = release.iter().map(|t| t.spotify_id.clone()).collect();
+
+            if let Err(e) = self.search_album(release).await {
+                tracing::error!(error = ?e, "failed to search album");
+                metrics::inc(metrics::SpotifyErrors, 1);
+            }
+
+            if release.iter().map(|t| &t.spotify_id).ne(&before) {
+                changed = true;
+            }
+        }
+
         for track in state.tracks.iter_mut() {
             let before = track.spotify_id.is_none();
 
@@ -326,3 +578,42 @@ impl Client {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bandcamp::{BlogMeta, BlogPost, TrackList};
+
+    #[test]
+    fn playlist_names() {
+        let post = BlogPost::new(
+            BlogMeta {
+                title: "Some Post".to_string(),
+                url: "https://daily.bandcamp.com/lists/some-post".to_string(),
+                published: "2024-03-04T13:00:00Z".parse().unwrap(),
+                modified: "2024-03-04T13:00:00Z".parse().unwrap(),
+                description: String::new(),
+                author: None,
+                series: Some("Lists".to_string()),
+                image: None,
+                tags: vec![],
+                reading_minutes: None,
+            },
+            TrackList::default(),
+        );
+
+        // existing playlists are found by name, so the default can't change
+        assert_eq!(
+            "Bandcamp - 2024-03-04 - Some Post",
+            playlist_name(DEFAULT_PLAYLIST_NAME, &post)
+        );
+        assert_eq!(
+            "Lists - Some Post",
+            playlist_name("{series} - {author} - {title}", &post)
+        );
+
+        assert!(parse_playlist_name("{series}: {title}").is_ok());
+        assert!(parse_playlist_name("{nope}").is_err());
+        assert!(parse_playlist_name("{title").is_err());
+    }
+}
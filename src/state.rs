@@ -1,9 +1,17 @@
-use crate::{bandcamp::BlogPost, types::Track};
+use crate::{
+    bandcamp::BlogPost,
+    feed::Entry,
+    types::{DateTime, Track},
+};
 use serde_json as json;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 
 pub(crate) const OUT_DIR: &str = "./data";
 const BLOG_INFO_FILENAME: &str = "info.json";
+const HTML_ARCHIVE_FILENAME: &str = "post.html.gz";
+const SEEN_FILENAME: &str = "seen.json";
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct State {
@@ -61,6 +69,23 @@ pub(crate) fn load<T: for<'de> serde::Deserialize<'de>>(fname: &PathBuf) -> anyh
     Ok(serde_json::from_reader::<_, T>(fh)?)
 }
 
+/// When `url` was first scanned, remembered between runs. Pages that don't
+/// say when they were published are dated by this, so that their directory
+/// stays put.
+pub(crate) fn first_seen(dir: &str, url: &str) -> anyhow::Result<DateTime> {
+    let path = PathBuf::from(dir).join(SEEN_FILENAME);
+    let mut seen: BTreeMap<String, DateTime> = load(&path).unwrap_or_default();
+
+    if let Some(date) = seen.get(url) {
+        return Ok(*date);
+    }
+
+    let now = chrono::Utc::now();
+    seen.insert(url.to_owned(), now);
+    save(&seen, &path)?;
+    Ok(now)
+}
+
 pub(crate) fn update<T>(t: &T, fname: &PathBuf) -> anyhow::Result<()>
 where
     T: serde::Serialize,
@@ -155,6 +180,12 @@ impl State {
                 }
             };
 
+            // left unset when the page's age isn't known, e.g. when rebuilt
+            // from the archive, so that the URL is refreshed before use
+            if new.download_url.is_some() && new.download_url_fetched.is_none() {
+                new.download_url_fetched = self.blog_info.fetched;
+            }
+
             tracks.push(new);
         }
 
@@ -206,6 +237,45 @@ impl State {
         Ok(())
     }
 
+    /// Saves the post's HTML, compressed, next to `info.json`.
+    pub(crate) fn archive_html(&self, html: &str) -> anyhow::Result<()> {
+        let path = self.dirname().join(HTML_ARCHIVE_FILENAME);
+        let fh = std::fs::File::create(&path)?;
+
+        let mut gz = flate2::write::GzEncoder::new(fh, flate2::Compression::default());
+        gz.write_all(html.as_bytes())?;
+        gz.finish()?;
+
+        tracing::debug!(?path, "archived post HTML");
+        Ok(())
+    }
+
+    fn has_archived_html(&self) -> bool {
+        self.dirname().join(HTML_ARCHIVE_FILENAME).is_file()
+    }
+
+    pub(crate) fn archived_html(&self) -> anyhow::Result<String> {
+        let path = self.dirname().join(HTML_ARCHIVE_FILENAME);
+
+        let mut html = String::new();
+        flate2::read::GzDecoder::new(std::fs::File::open(&path)?).read_to_string(&mut html)?;
+        Ok(html)
+    }
+
+    /// The feed entry this post came from, as far as we remember it.
+    pub(crate) fn entry(&self) -> Entry {
+        let post = &self.blog_info;
+
+        Entry {
+            url: post.url.clone(),
+            title: Some(post.title.clone()),
+            categories: post.categories.clone(),
+            author: post.author.clone(),
+            published: Some(post.published),
+            extractor: post.extractor,
+        }
+    }
+
     pub(crate) fn need_save(&mut self) {
         self.need_save = true;
     }
@@ -232,13 +302,28 @@ impl State {
                 .any(|t| t.spotify_id.is_none() || t.spotify_playlist_id.is_none())
     }
 
+    /// Tracks backing off after failed downloads don't count until they're
+    /// due again.
     pub(crate) fn needs_downloads(&self) -> bool {
+        let now = chrono::Utc::now();
+
         self.tracks.iter().any(|track| {
             let path = self.dirname().join(track.mp3_filename());
             !path.exists()
+                && track
+                    .download_failures
+                    .as_ref()
+                    .is_none_or(|failures| failures.is_due(now))
         })
     }
 
+    /// Whether there is anything left to do for this post with the given
+    /// options.
+    pub(crate) fn needs_work(&self, args: &crate::cli::Args) -> bool {
+        (!args.no_spotify && self.needs_spotify_updates())
+            || (!args.no_download && self.needs_downloads())
+    }
+
     fn cleanup_files(&self) -> anyhow::Result<()> {
         let dir = self.dirname();
 
@@ -288,41 +373,88 @@ impl State {
     }
 }
 
-pub(crate) fn load_blogs(dir: &str) -> anyhow::Result<Vec<State>> {
+/// Loads every saved post without touching the files on disk.
+fn read_blogs(dir: &str) -> anyhow::Result<Vec<State>> {
     Ok(std::fs::read_dir(dir)?
         .filter_map(|child| {
             let child = child.ok()?;
             let fname = child.path().join(BLOG_INFO_FILENAME);
             let mut state = load::<State>(&fname).ok()?;
             state.rehydrate_tracks().ok()?;
-            if let Err(e) = state.cleanup_files() {
-                tracing::warn!(
-                    "Error with filename normalization for {}: {}",
-                    state.blog_info.title,
-                    e,
-                );
-            }
             Some(state)
         })
         .collect())
 }
 
-pub(crate) fn blog_urls(args: &crate::cli::Args) -> anyhow::Result<Vec<String>> {
+/// Loads every saved post, tidying up duplicate and stray track files.
+pub(crate) fn load_blogs(dir: &str) -> anyhow::Result<Vec<State>> {
+    let states = read_blogs(dir)?;
+
+    for state in &states {
+        if let Err(e) = state.cleanup_files() {
+            tracing::warn!(
+                "Error with filename normalization for {}: {}",
+                state.blog_info.title,
+                e,
+            );
+        }
+    }
+
+    Ok(states)
+}
+
+pub(crate) fn blog_entries(args: &crate::cli::Args) -> anyhow::Result<Vec<Entry>> {
     let states = load_blogs(&args.download_to)?;
 
-    let mut urls = Vec::with_capacity(states.len());
+    let mut entries = Vec::with_capacity(states.len());
 
     for state in states.into_iter() {
-        if !args.no_spotify && state.needs_spotify_updates() {
-            urls.push(state.blog_info.url);
+        if !args.wants_categories(&state.blog_info.filter_categories())
+            || !args.in_date_range(&state.blog_info.published)
+        {
             continue;
         }
 
-        if !args.no_download && state.needs_downloads() {
-            urls.push(state.blog_info.url);
+        if state.needs_work(args) {
+            entries.push(state.entry());
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Saved posts with archived HTML, for `--offline`. The HTML is left on
+/// disk until [`State::archived_html`] is called.
+pub(crate) fn archived_posts(args: &crate::cli::Args) -> anyhow::Result<Vec<State>> {
+    let mut posts = vec![];
+
+    for state in load_blogs(&args.download_to)? {
+        if !args.wants_categories(&state.blog_info.filter_categories())
+            || !args.in_date_range(&state.blog_info.published)
+        {
             continue;
         }
+
+        if state.has_archived_html() {
+            posts.push(state);
+        } else {
+            tracing::debug!(state.blog_info.url, "SKIP: no archived HTML");
+        }
     }
 
-    Ok(urls)
+    Ok(posts)
+}
+
+/// URLs of posts that have been fully processed already.
+pub(crate) fn complete_urls(args: &crate::cli::Args) -> anyhow::Result<HashSet<String>> {
+    if !Path::new(&args.download_to).is_dir() {
+        return Ok(HashSet::new());
+    }
+
+    // only a lookup, so leave the files alone
+    Ok(read_blogs(&args.download_to)?
+        .into_iter()
+        .filter(|state| !state.needs_work(args))
+        .map(|state| state.blog_info.url)
+        .collect())
 }
@@ -1,5 +1,6 @@
 use crate::metrics;
-use id3::{frame::ExtendedText, Tag, TagLike, Version};
+use id3::frame::{Comment, ExtendedText, Picture, PictureType};
+use id3::{Tag, TagLike, Version};
 use std::collections::HashMap;
 
 pub(crate) async fn tag(state: &crate::state::State) -> anyhow::Result<()> {
@@ -89,10 +90,52 @@ pub(crate) async fn tag(state: &crate::state::State) -> anyhow::Result<()> {
             &track.album_artist.spotify_id,
         );
 
+        let post = &state.blog_info;
+        set_tag(&mut tag, "bandcamp_post_url", &Some(post.url.clone()));
+        set_tag(&mut tag, "bandcamp_post_author", &post.author);
+        set_tag(&mut tag, "bandcamp_post_series", &post.series);
+        set_tag(
+            &mut tag,
+            "bandcamp_post_tags",
+            &Some(post.tags.join("; ")).filter(|tags| !tags.is_empty()),
+        );
+
         set_tag(&mut tag, "bandcamp_album_id", &track.album.bandcamp_id);
         set_tag(&mut tag, "bandcamp_album_url", &track.album.bandcamp_url);
         set_tag(&mut tag, "spotify_album_id", &track.album.spotify_id);
 
+        if let Some(blurb) = &track.blurb {
+            let current = tag.comments().find(|c| c.description.is_empty());
+
+            if current.is_none_or(|c| c.text != *blurb) {
+                updated = true;
+                tag.remove_comment(Some(""), None);
+                tag.add_frame(Comment {
+                    lang: "eng".to_string(),
+                    description: String::new(),
+                    text: blurb.clone(),
+                });
+            }
+        }
+
+        let cover = crate::cover::path(state, track);
+        if let Ok(data) = tokio::fs::read(&cover).await {
+            let current = tag
+                .pictures()
+                .find(|pic| pic.picture_type == PictureType::CoverFront);
+
+            if current.is_none_or(|pic| pic.data != data) {
+                updated = true;
+                tag.remove_picture_by_type(PictureType::CoverFront);
+                tag.add_frame(Picture {
+                    mime_type: "image/jpeg".to_string(),
+                    picture_type: PictureType::CoverFront,
+                    description: String::new(),
+                    data,
+                });
+            }
+        }
+
         if updated {
             tracing::info!(?fname, "tags updated, saving file");
             tag.write_to_path(fname, Version::Id3v24)?;
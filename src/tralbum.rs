@@ -0,0 +1,257 @@
+//! Release details scraped from a Bandcamp album or track page.
+
+use crate::bandcamp::selector;
+use crate::metrics;
+use crate::state::State;
+use crate::types::{AlbumDetails, DateTime, Track};
+use anyhow::Context;
+use scraper::Html;
+use serde_json as json;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+selector!(pub(crate) TRALBUM_DATA, "script[data-tralbum]");
+selector!(LD_JSON, r#"script[type="application/ld+json"]"#);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct TralbumTrack {
+    pub(crate) number: Option<usize>,
+    pub(crate) title: String,
+    pub(crate) lyrics: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Tralbum {
+    pub(crate) details: AlbumDetails,
+    pub(crate) tracks: Vec<TralbumTrack>,
+}
+
+pub(crate) fn parse_date(s: &str) -> Option<DateTime> {
+    if let Ok(date) = s.parse() {
+        return Some(date);
+    }
+
+    // "05 Jan 2024 00:00:00 GMT"
+    chrono::NaiveDateTime::parse_from_str(s, "%d %b %Y %H:%M:%S GMT")
+        .ok()
+        .map(|date| date.and_utc())
+}
+
+fn string(value: &json::Value) -> Option<String> {
+    value
+        .as_str()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_owned)
+}
+
+fn lyrics(item: &json::Value) -> Option<String> {
+    string(&item["recordingOf"]["lyrics"]["text"])
+}
+
+impl Tralbum {
+    pub(crate) fn from_html(html: &str) -> anyhow::Result<Self> {
+        let doc = Html::parse_document(html);
+
+        let tralbum: Option<json::Value> = TRALBUM_DATA
+            .select(&doc)
+            .find_map(|elem| elem.attr("data-tralbum"))
+            .map(json::from_str)
+            .transpose()
+            .context("parsing 'data-tralbum' as JSON")?;
+
+        let ld: Option<json::Value> = LD_JSON
+            .select(&doc)
+            .map(|elem| elem.text().collect::<String>())
+            .find_map(|text| json::from_str(&text).ok());
+
+        if tralbum.is_none() && ld.is_none() {
+            anyhow::bail!("no release data found in page");
+        }
+
+        let tralbum = tralbum.unwrap_or_default();
+        let ld = ld.unwrap_or_default();
+
+        let artist = string(&ld["byArtist"]["name"]).or_else(|| string(&tralbum["artist"]));
+
+        let label = string(&ld["recordLabel"]["name"]).or_else(|| {
+            string(&ld["publisher"]["name"]).filter(|name| Some(name) != artist.as_ref())
+        });
+
+        let tags = match &ld["keywords"] {
+            json::Value::Array(tags) => tags.iter().filter_map(string).collect(),
+            json::Value::String(tags) => tags
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            _ => vec![],
+        };
+
+        let offers = &ld["albumRelease"][0]["offers"];
+        let offers = if offers.is_null() {
+            &ld["offers"]
+        } else {
+            offers
+        };
+        let price = match (offers["price"].as_f64(), string(&offers["priceCurrency"])) {
+            (Some(price), Some(currency)) => Some(format!("{price:.2} {currency}")),
+            _ => None,
+        };
+
+        let details = AlbumDetails {
+            release_date: string(&ld["datePublished"])
+                .or_else(|| string(&tralbum["album_release_date"]))
+                .or_else(|| string(&tralbum["current"]["release_date"]))
+                .and_then(|date| parse_date(&date)),
+            label,
+            tags,
+            credits: string(&ld["creditText"]).or_else(|| string(&tralbum["current"]["credits"])),
+            price,
+            artwork_url: string(&ld["image"]),
+        };
+
+        let tracks = match ld["track"]["itemListElement"].as_array() {
+            Some(items) => items
+                .iter()
+                .map(|elem| TralbumTrack {
+                    number: elem["position"].as_u64().map(|n| n as usize),
+                    title: string(&elem["item"]["name"]).unwrap_or_default(),
+                    lyrics: lyrics(&elem["item"]),
+                })
+                .collect(),
+
+            // track pages describe a single recording
+            None => string(&ld["name"])
+                .map(|title| TralbumTrack {
+                    number: None,
+                    title,
+                    lyrics: lyrics(&ld),
+                })
+                .into_iter()
+                .collect(),
+        };
+
+        Ok(Self { details, tracks })
+    }
+
+    pub(crate) async fn try_from_url(
+        url: &str,
+        client: &crate::http::Client,
+    ) -> anyhow::Result<Self> {
+        let html = client.fetch_text(url).await?;
+        Self::from_html(&html)
+    }
+
+    fn track(&self, track: &Track) -> Option<&TralbumTrack> {
+        if let [only] = &self.tracks[..] {
+            return Some(only);
+        }
+
+        self.tracks
+            .iter()
+            .find(|t| t.number == Some(track.number) && t.title == track.title)
+            .or_else(|| self.tracks.iter().find(|t| t.title == track.title))
+    }
+}
+
+/// Fills in release details for tracks that don't have them yet. Details are
+/// saved with the tracks, so each release page is only fetched once; pages
+/// shared between posts come from the page cache.
+pub(crate) async fn enrich(state: &mut State, client: &crate::http::Client) {
+    let mut changed = false;
+    // tracks from the same release share a page
+    let mut pages = HashMap::new();
+
+    for track in state.tracks.iter_mut() {
+        if track.album.details.is_some() {
+            continue;
+        }
+
+        let Some(url) = track.album.bandcamp_url.clone() else {
+            continue;
+        };
+
+        let page = match pages.entry(url) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match Tralbum::try_from_url(entry.key(), client).await {
+                Ok(page) => entry.insert(page),
+                Err(e) => {
+                    tracing::warn!(
+                        url = entry.key(),
+                        track.title,
+                        "failed fetching release page: {e}"
+                    );
+                    continue;
+                }
+            },
+        };
+
+        track.album.details = Some(page.details.clone());
+        if let Some(lyrics) = page.track(track).and_then(|t| t.lyrics.clone()) {
+            track.lyrics = Some(lyrics);
+        }
+
+        changed = true;
+        metrics::inc(metrics::TracksEnriched, 1);
+    }
+
+    if changed {
+        state.need_save_tracks();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tralbum_from_html() {
+        let html = r#"<html><head>
+<script type="application/ld+json">
+{
+  "@type": "MusicAlbum",
+  "name": "Some Album",
+  "byArtist": {"@type": "MusicGroup", "name": "Some Artist"},
+  "publisher": {"@type": "MusicGroup", "name": "Some Label"},
+  "datePublished": "05 Jan 2024 00:00:00 GMT",
+  "keywords": ["electronic", "ambient", "Berlin"],
+  "creditText": "Mastered by someone",
+  "image": "https://f4.bcbits.com/img/a0123456789_10.jpg",
+  "albumRelease": [{"offers": {"price": 7.0, "priceCurrency": "EUR"}}],
+  "track": {"itemListElement": [
+    {"position": 1, "item": {"name": "First"}},
+    {"position": 2, "item": {"name": "Second", "recordingOf": {"lyrics": {"text": "la la la"}}}}
+  ]}
+}
+</script>
+<script data-tralbum="{&quot;artist&quot;:&quot;Some Artist&quot;}"></script>
+</head><body></body></html>"#;
+
+        let page = Tralbum::from_html(html).unwrap();
+
+        assert_eq!(
+            AlbumDetails {
+                release_date: Some("2024-01-05T00:00:00Z".parse().unwrap()),
+                label: Some("Some Label".to_string()),
+                tags: vec![
+                    "electronic".to_string(),
+                    "ambient".to_string(),
+                    "Berlin".to_string()
+                ],
+                credits: Some("Mastered by someone".to_string()),
+                price: Some("7.00 EUR".to_string()),
+                artwork_url: Some("https://f4.bcbits.com/img/a0123456789_10.jpg".to_string()),
+            },
+            page.details
+        );
+
+        let mut track = Track::new("Second", "Some Artist", "Some Album");
+        track.number = 2;
+        assert_eq!(
+            Some("la la la"),
+            page.track(&track).and_then(|t| t.lyrics.as_deref())
+        );
+    }
+}
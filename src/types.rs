@@ -33,12 +33,31 @@ where
     }
 }
 
+/// Release details from the album/track page on Bandcamp
+#[derive(Debug, Eq, PartialEq, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub(crate) struct AlbumDetails {
+    pub(crate) release_date: Option<DateTime>,
+    pub(crate) label: Option<String>,
+    pub(crate) tags: Vec<String>,
+    pub(crate) credits: Option<String>,
+    pub(crate) price: Option<String>,
+    pub(crate) artwork_url: Option<String>,
+}
+
 #[derive(Debug, Eq, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct Album {
     pub(crate) title: String,
     pub(crate) bandcamp_id: Option<String>,
     pub(crate) bandcamp_url: Option<String>,
     pub(crate) spotify_id: Option<String>,
+
+    /// Bandcamp image id of the release artwork
+    #[serde(default)]
+    pub(crate) art_id: Option<u64>,
+
+    /// `None` until the release page has been fetched
+    #[serde(default)]
+    pub(crate) details: Option<AlbumDetails>,
 }
 
 #[cfg(test)]
@@ -49,6 +68,8 @@ impl Album {
             bandcamp_id: Default::default(),
             bandcamp_url: Default::default(),
             spotify_id: Default::default(),
+            art_id: Default::default(),
+            details: Default::default(),
         }
     }
 }
@@ -63,6 +84,23 @@ where
     }
 }
 
+/// Failed attempts at downloading a track, cleared once one succeeds
+#[derive(Debug, Eq, PartialEq, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub(crate) struct DownloadFailures {
+    pub(crate) attempts: u32,
+    pub(crate) last_error: Option<String>,
+    pub(crate) last_status: Option<u16>,
+
+    /// Not worth trying again before this
+    pub(crate) next_retry: Option<DateTime>,
+}
+
+impl DownloadFailures {
+    pub(crate) fn is_due(&self, now: DateTime) -> bool {
+        self.next_retry.is_none_or(|next| next <= now)
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
 pub(crate) struct Track {
     pub(crate) title: String,
@@ -73,9 +111,32 @@ pub(crate) struct Track {
     pub(crate) number: usize,
     pub(crate) bandcamp_playlist_track_number: usize,
     pub(crate) download_url: Option<String>,
+
+    /// When `download_url` was scraped; stream URLs are signed and expire
+    #[serde(default)]
+    pub(crate) download_url_fetched: Option<DateTime>,
     pub(crate) bandcamp_track_id: Option<String>,
     pub(crate) spotify_id: Option<String>,
     pub(crate) spotify_playlist_id: Option<String>,
+
+    /// Part of a whole release captured in full-album mode, as opposed to
+    /// the single featured track of an embed
+    #[serde(default)]
+    pub(crate) full_album: bool,
+
+    #[serde(default)]
+    pub(crate) lyrics: Option<String>,
+
+    /// What the post had to say about this track
+    #[serde(default)]
+    pub(crate) blurb: Option<String>,
+
+    /// Why the last download was quarantined, until one succeeds
+    #[serde(default)]
+    pub(crate) invalid_download: Option<String>,
+
+    #[serde(default)]
+    pub(crate) download_failures: Option<DownloadFailures>,
 }
 
 #[cfg(test)]
@@ -95,9 +156,15 @@ impl Track {
             number: Default::default(),
             bandcamp_playlist_track_number: Default::default(),
             download_url: Default::default(),
+            download_url_fetched: Default::default(),
             bandcamp_track_id: Default::default(),
             spotify_id: Default::default(),
             spotify_playlist_id: Default::default(),
+            full_album: Default::default(),
+            lyrics: Default::default(),
+            blurb: Default::default(),
+            invalid_download: Default::default(),
+            download_failures: Default::default(),
         }
     }
 }
@@ -112,9 +179,23 @@ pub(crate) fn update<T: Clone + Eq>(old: &mut Option<T>, other: &Option<T>) -> b
 }
 
 impl Track {
+    /// Path relative to the post directory. Full-album tracks are grouped
+    /// into a directory per release and numbered by their album track number.
     pub(crate) fn filename(&self, ext: &str) -> PathBuf {
         let title = self.title.replace('/', "_");
         let artist = self.artist.name.replace('/', "_");
+
+        if self.full_album {
+            let album_artist = self.album_artist.name.replace('/', "_");
+            let album = self.album.title.replace('/', "_");
+            let dir = format!(
+                "{:02} - {} - {}",
+                self.bandcamp_playlist_track_number, album_artist, album
+            );
+            let fname = format!("{:02} - {} - {}.{}", self.number, artist, title, ext);
+            return PathBuf::from(dir).join(fname);
+        }
+
         let fname = format!(
             "{:02} - {} - {}.{}",
             self.bandcamp_playlist_track_number, artist, title, ext
@@ -135,10 +216,25 @@ impl Track {
         if mp3.exists() {
             // we already downloaded the mp3 successfully, so restore
             // the existing download url
-            self.download_url = from_disk.download_url;
+            self.download_url = from_disk.download_url.clone();
+        }
+
+        if self.download_url == from_disk.download_url {
+            self.download_url_fetched = from_disk.download_url_fetched;
         }
 
         self.spotify_id = from_disk.spotify_id;
+        self.invalid_download = from_disk.invalid_download;
+        self.download_failures = from_disk.download_failures;
         self.spotify_playlist_id = from_disk.spotify_playlist_id;
+
+        // release details are fetched separately from the blog post, so
+        // keep what we already have instead of fetching them again
+        if self.album.details.is_none() {
+            self.album.details = from_disk.album.details;
+        }
+        if self.lyrics.is_none() {
+            self.lyrics = from_disk.lyrics;
+        }
     }
 }
@@ -7,3 +7,70 @@ where
     let secs: f64 = serde::de::Deserialize::deserialize(deserializer)?;
     Ok(Duration::from_secs_f64(secs))
 }
+
+/// Lowercases `s` and collapses runs of non-alphanumeric characters into a
+/// single `-`, so that "Album of the Day" and "album-of-the-day" compare equal.
+pub(crate) fn slugify(s: &str) -> String {
+    s.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|seg| !seg.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Parses a duration like `90s`, `30m`, `1h30m` or `2d`.
+pub(crate) fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let mut total = 0u64;
+    let mut digits = String::new();
+
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => anyhow::bail!("invalid duration unit '{c}' in '{s}'"),
+        };
+
+        let n: u64 = digits
+            .parse()
+            .map_err(|_| anyhow::format_err!("invalid duration '{s}'"))?;
+        total += n * unit;
+        digits.clear();
+    }
+
+    if !digits.is_empty() || total == 0 {
+        anyhow::bail!("invalid duration '{s}' (expected something like 30m or 1h)");
+    }
+
+    Ok(Duration::from_secs(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(Duration::from_secs(90), parse_duration("90s").unwrap());
+        assert_eq!(Duration::from_secs(60 * 60), parse_duration("1h").unwrap());
+        assert_eq!(
+            Duration::from_secs(90 * 60),
+            parse_duration("1h30m").unwrap()
+        );
+        assert_eq!(
+            Duration::from_secs(2 * 24 * 60 * 60),
+            parse_duration("2d").unwrap()
+        );
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("1w").is_err());
+        assert!(parse_duration("0m").is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use crate::feed::{Entry, Feeds};
+use crate::types::{DateTime, Duration};
+use crate::{cli, shutdown, spotify, state};
+use futures::future;
+use futures::stream::{self, StreamExt};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(24 * 60 * 60);
+const RETRIES_FILENAME: &str = "watch-retries.json";
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Retry {
+    entry: Entry,
+    attempts: u32,
+    next: DateTime,
+}
+
+/// Posts that failed or still had work left over, retried with exponential
+/// backoff. Saved after every poll so that a restart doesn't forget them.
+#[derive(Debug)]
+struct Retries {
+    base: Duration,
+    path: PathBuf,
+    pending: BTreeMap<String, Retry>,
+}
+
+impl Retries {
+    fn load(dir: &str, base: Duration) -> Self {
+        let path = PathBuf::from(dir).join(RETRIES_FILENAME);
+
+        let pending = state::load(&path).unwrap_or_else(|e| {
+            tracing::debug!("no pending retries loaded from {path:?}: {e}");
+            BTreeMap::new()
+        });
+
+        Self {
+            base,
+            path,
+            pending,
+        }
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        state::save(&self.pending, &self.path)
+    }
+
+    fn is_due(&self, url: &str) -> bool {
+        self.pending
+            .get(url)
+            .is_none_or(|retry| retry.next <= chrono::Utc::now())
+    }
+
+    fn due(&self) -> impl Iterator<Item = &Entry> {
+        let now = chrono::Utc::now();
+        self.pending
+            .values()
+            .filter(move |retry| retry.next <= now)
+            .map(|retry| &retry.entry)
+    }
+
+    fn failed(&mut self, entry: &Entry) {
+        let retry = self
+            .pending
+            .entry(entry.url.clone())
+            .or_insert_with(|| Retry {
+                entry: entry.clone(),
+                attempts: 0,
+                next: chrono::Utc::now(),
+            });
+
+        retry.attempts += 1;
+        let delay = self
+            .base
+            .saturating_mul(2u32.saturating_pow(retry.attempts - 1))
+            .min(MAX_RETRY_DELAY);
+        retry.next = chrono::Utc::now() + chrono::Duration::from_std(delay).unwrap_or_default();
+
+        tracing::info!(
+            url = entry.url,
+            attempts = retry.attempts,
+            "post will be retried in {delay:?}"
+        );
+    }
+
+    fn succeeded(&mut self, url: &str) {
+        self.pending.remove(url);
+    }
+}
+
+async fn poll(
+    args: &cli::Args,
+    client: &crate::http::Client,
+    spotify: Option<&spotify::Client>,
+    retries: &mut Retries,
+) -> anyhow::Result<()> {
+    if let Some(spotify) = spotify {
+        spotify.refresh_token().await?;
+    }
+
+    let mut feeds = Feeds::load(&args.download_to);
+    let mut entries =
+        crate::skip_complete(args, crate::feed_entries(args, client, &mut feeds).await?)?;
+
+    let mut queue = |entry: &Entry| {
+        if !entries.iter().any(|e| e.url == entry.url) {
+            entries.push(entry.clone());
+        }
+    };
+
+    retries.due().for_each(&mut queue);
+
+    // posts from earlier runs with downloads or Spotify searches outstanding
+    state::blog_entries(args)?
+        .iter()
+        .filter(|entry| retries.is_due(&entry.url))
+        .for_each(&mut queue);
+
+    let mut posts = stream::iter(entries)
+        .take_while(|_| future::ready(!shutdown::requested()))
+        .map(|entry| async move {
+            let res = needs_retry(&entry, args, client, spotify).await;
+            (entry, res)
+        })
+        .buffered(crate::POSTS_IN_FLIGHT);
+
+    while let Some((entry, res)) = posts.next().await {
+        if res? {
+            retries.failed(&entry);
+        } else {
+            retries.succeeded(&entry.url);
+        }
+    }
+
+    if shutdown::requested() {
+        // leave the feed position alone so the rest are picked up again
+        return Ok(());
+    }
+
+    // anything that failed is in `retries`, which outlives the feed position
+    feeds.commit()
+}
+
+/// Fetches and processes a post, returning whether it needs another go.
+async fn needs_retry(
+    entry: &Entry,
+    args: &cli::Args,
+    client: &crate::http::Client,
+    spotify: Option<&spotify::Client>,
+) -> anyhow::Result<bool> {
+    let Some(post) = crate::fetch_post(entry, args, client, false).await? else {
+        return Ok(true);
+    };
+
+    if !args.in_date_range(&post.published) {
+        tracing::debug!(entry.url, "SKIP: outside of --since/--until");
+        return Ok(false);
+    }
+
+    match crate::process_post(post, args, client, spotify).await {
+        Ok(state) => Ok(state.needs_work(args)),
+        Err(e) => {
+            tracing::error!(?e, entry.url, "failed processing post");
+            Ok(true)
+        }
+    }
+}
+
+/// Polls the feeds every `interval` until SIGTERM/Ctrl-C.
+pub(crate) async fn watch(
+    args: &cli::Args,
+    interval: Duration,
+    client: &crate::http::Client,
+) -> anyhow::Result<()> {
+    shutdown::listen()?;
+
+    let spotify = crate::spotify_client(args).await?;
+    let mut retries = Retries::load(&args.download_to, interval);
+
+    while !shutdown::requested() {
+        if let Err(e) = poll(args, client, spotify.as_ref(), &mut retries).await {
+            tracing::error!(?e, "poll failed");
+        }
+
+        if let Err(e) = retries.save() {
+            tracing::error!(?e, "failed saving pending retries");
+        }
+
+        if shutdown::requested() {
+            break;
+        }
+
+        tracing::info!("next poll in {interval:?}");
+        shutdown::sleep(interval).await;
+    }
+
+    tracing::info!("shutting down");
+    Ok(())
+}